@@ -0,0 +1,77 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+
+//! Compares the symbol matching engines on one generated 500-symbol table.
+//!
+//! Run with `cargo bench --bench engines`. Each figure is the best of
+//! several runs; both engines must produce the same output.
+use ahash::AHashMap;
+use phirust_transpiler::{MatchEngine, SymbolTranspiler, ThreatDetector};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SYMBOLS: usize = 500;
+const LINES: usize = 20_000;
+const RUNS: usize = 5;
+
+// Half operator-like symbols from the mathematical operators block, half
+// word-like ones that need identifier boundaries.
+fn table() -> AHashMap<String, String> {
+    (0..SYMBOLS)
+        .map(|i| {
+            let symbol = if i % 2 == 0 {
+                char::from_u32(0x2200 + i as u32 / 2).unwrap().to_string()
+            } else {
+                format!("φ{}", i)
+            };
+            (symbol, format!("r{}", i))
+        })
+        .collect()
+}
+
+fn source(mappings: &AHashMap<String, String>) -> String {
+    let mut symbols: Vec<&String> = mappings.keys().collect();
+    symbols.sort();
+    (0..LINES)
+        .map(|i| {
+            let a = symbols[i % symbols.len()];
+            let b = symbols[(i * 7 + 3) % symbols.len()];
+            format!("x{i} = {a}(y, \"{b} in a string\") + {b}  # {a} in a comment\n")
+        })
+        .collect()
+}
+
+fn best<T>(mut run: impl FnMut() -> T) -> (T, Duration) {
+    let mut fastest = Duration::MAX;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = black_box(run());
+        fastest = fastest.min(start.elapsed());
+        result = Some(value);
+    }
+    (result.unwrap(), fastest)
+}
+
+fn main() {
+    let mappings = table();
+    let source = source(&mappings);
+    let detector = ThreatDetector::new().unwrap();
+    let mut outputs = Vec::new();
+
+    println!("{} symbols, {} bytes of source, best of {} runs", mappings.len(), source.len(), RUNS);
+    for engine in [MatchEngine::Regex, MatchEngine::AhoCorasick] {
+        let (mut transpiler, compiled) = best(|| {
+            let mut transpiler = SymbolTranspiler::new();
+            transpiler.set_engine(engine).unwrap();
+            transpiler.configure(mappings.clone()).unwrap();
+            transpiler
+        });
+        let (output, transpiled) = best(|| transpiler.transpile(&source, &detector, false).unwrap());
+        println!("{:<12} compile {:>10.2?}  transpile {:>10.2?}  {:>7.1} MB/s",
+            format!("{:?}", engine), compiled, transpiled, source.len() as f64 / transpiled.as_secs_f64() / 1e6);
+        outputs.push(output);
+    }
+    assert!(outputs.windows(2).all(|pair| pair[0] == pair[1]), "engines disagree");
+}
@@ -0,0 +1,664 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::lexer::{self, SegmentKind};
+use crate::policy::ThreatRule;
+use ahash::{AHashMap, AHashSet};
+use std::ops::Range;
+
+// Threat rules re-expressed as resolved Python names, so `os.system(` becomes
+// a call to `os.system` no matter how `os` or `system` were imported.
+#[derive(Debug, Default)]
+pub(crate) struct NameRules {
+    calls: AHashMap<String, usize>,
+    names: AHashMap<String, usize>,
+    prefixes: Vec<(String, usize)>,
+    getattr_builtins: Option<usize>,
+}
+
+impl NameRules {
+    // Splits rules into the ones expressible as names and the indices of those
+    // that are not, which stay with the substring matcher.
+    pub(crate) fn new(rules: &[ThreatRule]) -> (Self, Vec<usize>) {
+        let mut name_rules = Self::default();
+        let mut leftover = Vec::new();
+        for (index, rule) in rules.iter().enumerate() {
+            let pattern = rule.pattern.trim();
+            if pattern.starts_with("getattr(__builtins__") {
+                name_rules.getattr_builtins.get_or_insert(index);
+            } else if let Some(name) = pattern.strip_suffix('(').map(str::trim_end).filter(|n| is_dotted_name(n)) {
+                name_rules.calls.entry(normalize(name)).or_insert(index);
+            } else if let Some(module) = pattern.strip_suffix('.').filter(|n| is_dotted_name(n)) {
+                name_rules.prefixes.push((module.to_string(), index));
+            } else if is_dotted_name(pattern) {
+                name_rules.names.entry(normalize(pattern)).or_insert(index);
+            } else {
+                leftover.push(index);
+            }
+        }
+        (name_rules, leftover)
+    }
+
+    // `used` is whether the name is called or loaded as a value; a call rule
+    // also matches qualified references such as `os.system` or an alias.
+    fn lookup(&self, resolved: &str, used: bool, qualified: bool) -> Option<usize> {
+        if let Some(&index) = self.names.get(resolved) {
+            return Some(index);
+        }
+        if let Some(&index) = self.calls.get(resolved)
+            && (used || qualified)
+        {
+            return Some(index);
+        }
+        self.prefixes.iter()
+            .find(|(module, _)| resolved.len() > module.len()
+                && resolved.starts_with(module.as_str())
+                && resolved.as_bytes()[module.len()] == b'.')
+            .map(|&(_, index)| index)
+    }
+
+    fn lookup_module(&self, module: &str) -> Option<usize> {
+        self.prefixes.iter()
+            .find(|(prefix, _)| module == prefix || module.starts_with(&format!("{}.", prefix)))
+            .map(|&(_, index)| index)
+    }
+
+    /// Resolves calls, attribute accesses and imports in `code`, returning the
+    /// byte range and rule index of every dangerous use.
+    pub(crate) fn find(&self, code: &str) -> Vec<(Range<usize>, usize)> {
+        let tokens = tokenize(code);
+        let mut analyzer = Analyzer::new(self, &tokens);
+        analyzer.run();
+        analyzer.findings
+    }
+}
+
+/// Every module named by an absolute `import` or `from ... import` statement in
+/// `code`, with the byte range of the name. `from a import b` yields `a.b`.
+pub(crate) fn imports(code: &str) -> Vec<(String, Range<usize>)> {
+    let rules = NameRules::default();
+    let tokens = tokenize(code);
+    let mut analyzer = Analyzer::new(&rules, &tokens);
+    analyzer.run();
+    analyzer.imports
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind<'a> {
+    Name(&'a str),
+    Str(String),
+    Op(u8),
+    Newline,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    kind: Kind<'a>,
+    range: Range<usize>,
+}
+
+fn tokenize(code: &str) -> Vec<Token<'_>> {
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    for segment in lexer::segments(code) {
+        let range = segment.range;
+        if segment.kind == SegmentKind::Literal {
+            if !code[range.clone()].starts_with('#') {
+                tokens.push(Token { kind: Kind::Str(string_value(&code[range.clone()])), range });
+            }
+            continue;
+        }
+
+        let mut pos = range.start;
+        while pos < range.end {
+            let byte = bytes[pos];
+            let start = pos;
+            if is_name_start(byte) {
+                while pos < range.end && is_name_byte(bytes[pos]) {
+                    pos += 1;
+                }
+                tokens.push(Token { kind: Kind::Name(&code[start..pos]), range: start..pos });
+                continue;
+            }
+            pos += 1;
+            match byte {
+                b'\\' if bytes.get(pos) == Some(&b'\n') => pos += 1,
+                b'\\' if bytes.get(pos) == Some(&b'\r') => {
+                    pos += if bytes.get(pos + 1) == Some(&b'\n') { 2 } else { 1 };
+                },
+                b'\n' | b'\r' => {
+                    if depth == 0 {
+                        tokens.push(Token { kind: Kind::Newline, range: start..pos });
+                    }
+                },
+                b' ' | b'\t' | b'\x0c' => {},
+                b'0'..=b'9' => {
+                    while pos < range.end && (is_name_byte(bytes[pos]) || bytes[pos] == b'.') {
+                        pos += 1;
+                    }
+                    tokens.push(Token { kind: Kind::Other, range: start..pos });
+                },
+                b'(' | b'[' | b'{' => {
+                    depth += 1;
+                    tokens.push(Token { kind: Kind::Op(byte), range: start..pos });
+                },
+                b')' | b']' | b'}' => {
+                    depth = depth.saturating_sub(1);
+                    tokens.push(Token { kind: Kind::Op(byte), range: start..pos });
+                },
+                _ => tokens.push(Token { kind: Kind::Op(byte), range: start..pos }),
+            }
+        }
+    }
+    tokens
+}
+
+struct Analyzer<'r, 't, 'a> {
+    rules: &'r NameRules,
+    tokens: &'t [Token<'a>],
+    aliases: AHashMap<String, String>,
+    // Names rebound to something other than a name, so loading them is harmless.
+    shadowed: AHashSet<String>,
+    // Indices of parameter name tokens in `def` headers.
+    parameters: AHashSet<usize>,
+    findings: Vec<(Range<usize>, usize)>,
+    imports: Vec<(String, Range<usize>)>,
+}
+
+impl<'r, 't, 'a> Analyzer<'r, 't, 'a> {
+    fn new(rules: &'r NameRules, tokens: &'t [Token<'a>]) -> Self {
+        Self {
+            rules,
+            tokens,
+            aliases: AHashMap::new(),
+            shadowed: AHashSet::new(),
+            parameters: AHashSet::new(),
+            findings: Vec::new(),
+            imports: Vec::new(),
+        }
+    }
+
+    fn kind(&self, index: usize) -> Option<&Kind<'a>> {
+        self.tokens.get(index).map(|t| &t.kind)
+    }
+
+    fn is_op(&self, index: usize, op: u8) -> bool {
+        self.kind(index) == Some(&Kind::Op(op))
+    }
+
+    fn is_name(&self, index: usize, name: &str) -> bool {
+        matches!(self.kind(index), Some(Kind::Name(n)) if *n == name)
+    }
+
+    fn ends_statement(&self, index: usize) -> bool {
+        matches!(self.kind(index), None | Some(Kind::Newline) | Some(Kind::Op(b';')))
+    }
+
+    fn run(&mut self) {
+        let mut index = 0;
+        let mut statement_start = true;
+        let mut depth = 0usize;
+        while index < self.tokens.len() {
+            if statement_start {
+                if self.is_name(index, "import") {
+                    index = self.import(index + 1);
+                    continue;
+                }
+                if self.is_name(index, "from") {
+                    index = self.import_from(index + 1);
+                    continue;
+                }
+                self.record_alias(index);
+            }
+
+            statement_start = false;
+            match &self.tokens[index].kind {
+                Kind::Newline => statement_start = true,
+                Kind::Op(b';') => statement_start = true,
+                Kind::Op(b':') if depth == 0 => statement_start = true,
+                Kind::Op(b'(' | b'[' | b'{') => depth += 1,
+                Kind::Op(b')' | b']' | b'}') => depth = depth.saturating_sub(1),
+                Kind::Name(name) => {
+                    let name = *name;
+                    let after_dot = index > 0 && self.is_op(index - 1, b'.');
+                    let defined = index > 0 && (self.is_name(index - 1, "def") || self.is_name(index - 1, "class"));
+                    if defined {
+                        self.shadowed.insert(name.to_string());
+                        if self.is_name(index - 1, "def") {
+                            self.mark_parameters(index + 1);
+                        }
+                    }
+                    if !after_dot && !defined && !self.parameters.contains(&index) {
+                        index = self.expression(index);
+                        continue;
+                    }
+                },
+                _ => {},
+            }
+            index += 1;
+        }
+    }
+
+    // Reads `a.b.c` starting at `index`, returning the parts and the index after.
+    fn chain(&self, mut index: usize) -> (Vec<&'a str>, usize) {
+        let mut parts = Vec::new();
+        while let Some(Kind::Name(name)) = self.kind(index) {
+            parts.push(*name);
+            index += 1;
+            if self.is_op(index, b'.') && matches!(self.kind(index + 1), Some(Kind::Name(_))) {
+                index += 1;
+            } else {
+                break;
+            }
+        }
+        (parts, index)
+    }
+
+    fn resolve(&self, parts: &[&str]) -> String {
+        let mut resolved = self.aliases.get(parts[0]).cloned().unwrap_or_else(|| parts[0].to_string());
+        for part in &parts[1..] {
+            resolved.push('.');
+            resolved.push_str(part);
+        }
+        normalize(&resolved)
+    }
+
+    fn flag(&mut self, range: Range<usize>, rule: usize) {
+        self.findings.push((range, rule));
+    }
+
+    fn expression(&mut self, start: usize) -> usize {
+        let (parts, mut end) = self.chain(start);
+        let mut resolved = self.resolve(&parts);
+        let mut qualified = parts.len() > 1 || self.aliases.contains_key(parts[0]);
+
+        // `__builtins__['eval']` reads like an attribute access.
+        if is_builtins_module(&resolved)
+            && self.is_op(end, b'[')
+            && self.is_op(end + 2, b']')
+            && let Some(Kind::Str(key)) = self.kind(end + 1)
+        {
+            resolved = normalize(&format!("{}.{}", resolved, key));
+            qualified = true;
+            end += 3;
+        }
+
+        let called = self.is_op(end, b'(');
+        let span_end = if called { self.tokens[end].range.end } else { self.tokens[end - 1].range.end };
+        let span = self.tokens[start].range.start..span_end;
+
+        if called && resolved == "getattr" {
+            self.getattr(span.clone(), end + 1);
+        }
+        // Rebinding a name is not a use of what it used to refer to, but
+        // passing it on as a value, as in `map(eval, xs)`, is.
+        let assigned = self.is_op(end, b'=') && !self.is_op(end + 1, b'=');
+        let used = called || !self.shadowed.contains(parts[0]);
+        if !assigned
+            && let Some(rule) = self.rules.lookup(&resolved, used, qualified)
+        {
+            self.flag(span, rule);
+        }
+        end
+    }
+
+    fn getattr(&mut self, span: Range<usize>, args: usize) {
+        let (object_end, object) = self.argument(args);
+        let Some(object) = object else {
+            return;
+        };
+        let object = self.resolve(&object);
+        let attribute = if self.is_op(object_end, b',') { self.constant(object_end + 1) } else { None };
+
+        if is_builtins_module(&object)
+            && let Some(rule) = self.rules.getattr_builtins
+        {
+            self.flag(span, rule);
+            return;
+        }
+        if let Some(attribute) = attribute {
+            let resolved = normalize(&format!("{}.{}", object, attribute));
+            if let Some(rule) = self.rules.lookup(&resolved, true, true) {
+                self.flag(span, rule);
+            }
+        }
+    }
+
+    // Returns the index of the `,` or `)` ending the argument at `index`, and the
+    // argument as a dotted chain when it is one.
+    fn argument(&self, index: usize) -> (usize, Option<Vec<&'a str>>) {
+        let mut end = index;
+        let mut depth = 0usize;
+        while let Some(kind) = self.kind(end) {
+            match kind {
+                Kind::Op(b'(' | b'[' | b'{') => depth += 1,
+                Kind::Op(b')' | b']' | b'}') if depth == 0 => break,
+                Kind::Op(b')' | b']' | b'}') => depth -= 1,
+                Kind::Op(b',') if depth == 0 => break,
+                _ => {},
+            }
+            end += 1;
+        }
+        let (parts, after_chain) = self.chain(index);
+        (end, (!parts.is_empty() && after_chain == end).then_some(parts))
+    }
+
+    // Folds `'ev' + 'al'` style argument tokens into a constant string.
+    fn constant(&self, mut index: usize) -> Option<String> {
+        let mut value = String::new();
+        let mut expect_string = true;
+        loop {
+            match self.kind(index) {
+                Some(Kind::Str(s)) if expect_string => value.push_str(s),
+                Some(Kind::Op(b'+')) if !expect_string => {},
+                Some(Kind::Op(b',' | b')')) | None if !expect_string => return Some(value),
+                _ => return None,
+            }
+            expect_string = !expect_string;
+            index += 1;
+        }
+    }
+
+    fn record_alias(&mut self, index: usize) {
+        let Some(&Kind::Name(target)) = self.kind(index) else {
+            return;
+        };
+        if !self.is_op(index + 1, b'=') || self.is_op(index + 2, b'=') {
+            return;
+        }
+        let (parts, end) = self.chain(index + 2);
+        if !parts.is_empty() && self.ends_statement(end) {
+            let resolved = self.resolve(&parts);
+            self.aliases.insert(target.to_string(), resolved);
+            self.shadowed.remove(target);
+        } else {
+            self.shadowed.insert(target.to_string());
+        }
+    }
+
+    // Marks the parameter names of the `def` parameter list opening at `index`;
+    // defaults and annotations are still analysed.
+    fn mark_parameters(&mut self, mut index: usize) {
+        if !self.is_op(index, b'(') {
+            return;
+        }
+        let mut depth = 0usize;
+        let mut expect_name = true;
+        while let Some(kind) = self.kind(index) {
+            match kind {
+                Kind::Op(b'(' | b'[' | b'{') => depth += 1,
+                Kind::Op(b')' | b']' | b'}') if depth == 1 => return,
+                Kind::Op(b')' | b']' | b'}') => depth -= 1,
+                Kind::Op(b',') if depth == 1 => expect_name = true,
+                Kind::Op(b'*' | b'/') if depth == 1 => {},
+                Kind::Name(_) if depth == 1 && expect_name => {
+                    self.parameters.insert(index);
+                    expect_name = false;
+                },
+                Kind::Newline => return,
+                _ => expect_name = false,
+            }
+            index += 1;
+        }
+    }
+
+    fn import(&mut self, mut index: usize) -> usize {
+        loop {
+            let start = index;
+            let (parts, end) = self.chain(index);
+            if parts.is_empty() {
+                return self.skip_statement(index);
+            }
+            let module = parts.join(".");
+            index = end;
+            if self.is_name(index, "as")
+                && let Some(Kind::Name(alias)) = self.kind(index + 1)
+            {
+                self.aliases.insert(alias.to_string(), module.clone());
+                index += 2;
+            }
+            let range = self.tokens[start].range.start..self.tokens[end - 1].range.end;
+            if let Some(rule) = self.rules.lookup_module(&module) {
+                self.flag(range.clone(), rule);
+            }
+            self.imports.push((module, range));
+            if !self.is_op(index, b',') {
+                return self.skip_statement(index);
+            }
+            index += 1;
+        }
+    }
+
+    fn import_from(&mut self, mut index: usize) -> usize {
+        let mut relative = false;
+        while self.is_op(index, b'.') {
+            relative = true;
+            index += 1;
+        }
+        let (parts, end) = self.chain(index);
+        index = end;
+        if !self.is_name(index, "import") {
+            return self.skip_statement(index);
+        }
+        index += 1;
+        if self.is_op(index, b'(') {
+            index += 1;
+        }
+        let module = if relative { String::new() } else { parts.join(".") };
+
+        loop {
+            match self.kind(index).cloned() {
+                Some(Kind::Op(b'*')) => {
+                    if let Some(rule) = self.rules.lookup_module(&module) {
+                        self.flag(self.tokens[index].range.clone(), rule);
+                    }
+                    if !module.is_empty() {
+                        self.imports.push((module.clone(), self.tokens[index].range.clone()));
+                    }
+                    index += 1;
+                },
+                Some(Kind::Name(name)) => {
+                    let full = if module.is_empty() { name.to_string() } else { format!("{}.{}", module, name) };
+                    let range = self.tokens[index].range.clone();
+                    index += 1;
+                    let mut bound = name;
+                    if self.is_name(index, "as")
+                        && let Some(Kind::Name(alias)) = self.kind(index + 1)
+                    {
+                        bound = alias;
+                        index += 2;
+                    }
+                    if !module.is_empty() {
+                        self.aliases.insert(bound.to_string(), full.clone());
+                        let resolved = normalize(&full);
+                        if let Some(rule) = self.rules.lookup(&resolved, false, true).or_else(|| self.rules.lookup_module(&resolved)) {
+                            self.flag(range.clone(), rule);
+                        }
+                        self.imports.push((full, range));
+                    }
+                },
+                _ => return self.skip_statement(index),
+            }
+            if self.is_op(index, b',') {
+                index += 1;
+            } else {
+                return self.skip_statement(index);
+            }
+        }
+    }
+
+    fn skip_statement(&self, mut index: usize) -> usize {
+        while !self.ends_statement(index) {
+            index += 1;
+        }
+        index
+    }
+}
+
+fn normalize(name: &str) -> String {
+    for prefix in ["builtins.", "__builtins__."] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return rest.to_string();
+        }
+    }
+    name.to_string()
+}
+
+fn is_builtins_module(name: &str) -> bool {
+    name == "builtins" || name == "__builtins__"
+}
+
+fn is_dotted_name(text: &str) -> bool {
+    !text.is_empty() && text.split('.').all(|part| {
+        let mut bytes = part.bytes();
+        bytes.next().is_some_and(is_name_start) && bytes.all(is_name_byte)
+    })
+}
+
+fn is_name_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_' || byte >= 0x80
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    is_name_start(byte) || byte.is_ascii_digit()
+}
+
+// Best-effort value of a string literal: prefix and quotes stripped, escapes kept.
+fn string_value(literal: &str) -> String {
+    let body = literal.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let quote = match body.chars().next() {
+        Some(q @ ('\'' | '"')) => q,
+        _ => return String::new(),
+    };
+    let triple: String = std::iter::repeat_n(quote, 3).collect();
+    let delimiter = if body.starts_with(&triple) { triple.as_str() } else { &body[..1] };
+    let inner = &body[delimiter.len()..];
+    inner.strip_suffix(delimiter).unwrap_or(inner).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::ThreatPolicy;
+
+    // Each finding as the flagged text and the pattern of its rule.
+    fn flagged(code: &str) -> Vec<(String, String)> {
+        let policy = ThreatPolicy::builtin();
+        let (names, _) = NameRules::new(policy.rules());
+        names.find(code).into_iter()
+            .map(|(range, rule)| finding(&code[range], &policy.rules()[rule].pattern))
+            .collect()
+    }
+
+    fn finding(text: &str, pattern: &str) -> (String, String) {
+        (text.to_string(), pattern.to_string())
+    }
+
+    #[test]
+    fn tokens_skip_comments_continuations_and_bracketed_newlines() {
+        let code = "a.b(1,\n 2) # c\nx = 'v' \\\n + f'{y}'";
+        let kinds: Vec<Kind> = tokenize(code).into_iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [
+            Kind::Name("a"), Kind::Op(b'.'), Kind::Name("b"), Kind::Op(b'('), Kind::Other, Kind::Op(b','),
+            Kind::Other, Kind::Op(b')'), Kind::Newline,
+            Kind::Name("x"), Kind::Op(b'='), Kind::Str("v".to_string()), Kind::Op(b'+'),
+            Kind::Str("{".to_string()), Kind::Name("y"), Kind::Str(String::new()),
+        ]);
+    }
+
+    #[test]
+    fn rules_split_into_names_and_substrings() {
+        let policy = ThreatPolicy::builtin();
+        let (names, leftover) = NameRules::new(policy.rules());
+        assert!(names.calls.contains_key("eval") && names.calls.contains_key("os.system"));
+        assert!(names.names.contains_key("__import__"));
+        assert_eq!(names.prefixes.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), ["subprocess"]);
+        assert!(names.getattr_builtins.is_some());
+        assert!(leftover.is_empty());
+    }
+
+    #[test]
+    fn direct_calls_are_flagged() {
+        assert_eq!(flagged("eval('1')"), [finding("eval(", "eval(")]);
+        assert_eq!(flagged("x = exec ('1')"), [finding("exec (", "exec(")]);
+        assert_eq!(flagged("import os\nos.system('ls')"), [finding("os.system(", "os.system(")]);
+    }
+
+    #[test]
+    fn harmless_uses_are_not_flagged() {
+        for code in [
+            "evaluate(1)",
+            "x.eval(1)",
+            "def eval(): pass",
+            "def run(eval, *, exec=None, **compile): pass",
+            "eval = 1",
+            "eval = lambda x: x\nprint(eval)",
+            "'eval(1)'",
+            "# eval(1)",
+            "system('ls')",
+        ] {
+            assert!(flagged(code).is_empty(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn names_passed_as_values_are_flagged() {
+        assert_eq!(flagged("list(map(eval, ['1']))"), [finding("eval", "eval(")]);
+        assert_eq!(flagged("(eval)('1')"), [finding("eval", "eval(")]);
+        assert_eq!(flagged("f = [exec][0]"), [finding("exec", "exec(")]);
+        assert_eq!(flagged("def run(f=compile): pass"), [finding("compile", "compile(")]);
+    }
+
+    #[test]
+    fn aliases_are_followed() {
+        assert_eq!(flagged("e = eval\nx = 1\ne('1+1')"), [finding("eval", "eval("), finding("e(", "eval(")]);
+        // A qualified reference is flagged even when not called.
+        assert_eq!(flagged("s = os.system\ns('ls')"), [finding("os.system", "os.system("), finding("s(", "os.system(")]);
+        // A chain of aliases resolves to the original; passing an alias on
+        // counts as a qualified reference.
+        assert_eq!(flagged("a = exec\nb = a\nb('x')"), [finding("exec", "exec("), finding("a", "exec("), finding("b(", "exec(")]);
+    }
+
+    #[test]
+    fn imports_bind_and_flag_names() {
+        assert_eq!(flagged("from os import system as run\nrun('ls')"), [
+            finding("system", "os.system("),
+            finding("run(", "os.system("),
+        ]);
+        assert_eq!(flagged("import subprocess as sp\nsp.run([])"), [
+            finding("subprocess", "subprocess."),
+            finding("sp.run(", "subprocess."),
+        ]);
+        assert_eq!(flagged("from subprocess import *"), [finding("*", "subprocess.")]);
+    }
+
+    #[test]
+    fn builtins_access_is_resolved() {
+        assert_eq!(flagged("import builtins\nbuiltins.exec('x')"), [finding("builtins.exec(", "exec(")]);
+        assert_eq!(flagged("__builtins__['eval']('1')"), [finding("__builtins__['eval'](", "eval(")]);
+        assert_eq!(flagged("b = __builtins__\nb.eval('1')"), [finding("b.eval(", "eval(")]);
+    }
+
+    #[test]
+    fn getattr_is_folded() {
+        assert_eq!(flagged("getattr(__builtins__, 'ev' + 'al')('1')"), [finding("getattr(", "getattr(__builtins__")]);
+        assert_eq!(flagged("import os\ngetattr(os, 'sys' + 'tem')('ls')"), [finding("getattr(", "os.system(")]);
+        assert!(flagged("import os\ngetattr(os, name)('ls')").is_empty());
+        assert!(flagged("import os\ngetattr(os, 'path')").is_empty());
+    }
+
+    #[test]
+    fn imported_modules_are_listed() {
+        let code = "import os.path as p, json\nfrom xml.etree import ElementTree as ET\nfrom . import local\nfrom a import (b,\n c)\nx = 'import y'";
+        let modules: Vec<(String, &str)> = imports(code).into_iter().map(|(module, range)| (module, &code[range])).collect();
+        assert_eq!(modules, [
+            ("os.path".to_string(), "os.path"),
+            ("json".to_string(), "json"),
+            ("xml.etree.ElementTree".to_string(), "ElementTree"),
+            ("a.b".to_string(), "b"),
+            ("a.c".to_string(), "c"),
+        ]);
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use ahash::AHashMap;
+use crate::cache::{self, Cache, CacheStats};
+use crate::dialect::{DialectManifest, Mismatch};
+use crate::error::{Error, Result, SecurityViolation};
+use crate::mapping_file::MappingTable;
+use crate::matcher::MatchEngine;
+use crate::normalize::{Confusable, Confusables, Normalization};
+use crate::threat_detector::ThreatDetector;
+use crate::transpiler::{OutputScan, SymbolTranspiler};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How a batch run finds, transpiles and writes its files.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Mirror the source tree under this directory; `None` writes next to each source.
+    pub out_dir: Option<PathBuf>,
+    /// Extension given to outputs, without the dot.
+    pub extension: String,
+    /// Extensions of the files picked up when walking a directory.
+    pub source_extensions: Vec<String>,
+    /// Worker threads; 0 uses the available parallelism.
+    pub jobs: usize,
+    pub bypass: bool,
+    pub output_scan: OutputScan,
+    pub engine: MatchEngine,
+    pub normalization: Normalization,
+    /// Look-alikes transpiled as the symbols they imitate, with a warning.
+    pub confusables: Confusables,
+    /// Also write a Source Map v3 file next to every output.
+    pub source_maps: bool,
+    /// Sources are checked against this dialect's version.
+    pub dialect: Option<DialectManifest>,
+    /// Fail sources declaring another dialect version instead of warning.
+    pub strict_dialect: bool,
+    /// Reuse outputs stored in this [`Cache`] directory. Ignored when writing
+    /// source maps, which need a fresh transpilation.
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            out_dir: None,
+            extension: "py".to_string(),
+            source_extensions: vec!["φ".to_string()],
+            jobs: 0,
+            bypass: false,
+            output_scan: OutputScan::Off,
+            engine: MatchEngine::Regex,
+            normalization: Normalization::None,
+            confusables: Confusables::default(),
+            source_maps: false,
+            dialect: None,
+            strict_dialect: false,
+            cache_dir: None,
+        }
+    }
+}
+
+/// An input file and the directory its output path is mirrored from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub root: PathBuf,
+}
+
+/// What happened to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The output was written.
+    Transpiled,
+    /// The output already had the transpiled content and was left alone.
+    Unchanged,
+    /// The threat detector refused the file.
+    Blocked(Vec<SecurityViolation>),
+    /// The file could not be read, transpiled or written.
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub status: FileStatus,
+    /// The source in the configured normalization, kept for rendering
+    /// diagnostics; empty if it could not be read.
+    pub source: String,
+    pub warnings: Vec<SecurityViolation>,
+    /// Findings allowed because they came from trusted symbols.
+    pub exemptions: Vec<SecurityViolation>,
+    /// Look-alikes transpiled as symbols.
+    pub confusables: Vec<Confusable>,
+    pub dialect: Option<Mismatch>,
+    /// Whether the output came from the cache; `None` if it was not consulted.
+    pub cached: Option<bool>,
+}
+
+impl FileResult {
+    fn new(file: &SourceFile, output: PathBuf) -> Self {
+        Self {
+            input: file.path.clone(),
+            output,
+            status: FileStatus::Transpiled,
+            source: String::new(),
+            warnings: Vec::new(),
+            exemptions: Vec::new(),
+            confusables: Vec::new(),
+            dialect: None,
+            cached: None,
+        }
+    }
+}
+
+/// Counts of file outcomes in a batch run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub transpiled: usize,
+    pub unchanged: usize,
+    pub blocked: usize,
+    pub failed: usize,
+    pub cache: CacheStats,
+}
+
+impl Summary {
+    pub fn new(results: &[FileResult]) -> Self {
+        let mut summary = Self::default();
+        for result in results {
+            match result.cached {
+                Some(true) => summary.cache.hits += 1,
+                Some(false) => summary.cache.misses += 1,
+                None => {},
+            }
+            match result.status {
+                FileStatus::Transpiled => summary.transpiled += 1,
+                FileStatus::Unchanged => summary.unchanged += 1,
+                FileStatus::Blocked(_) => summary.blocked += 1,
+                FileStatus::Failed(_) => summary.failed += 1,
+            }
+        }
+        summary
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.blocked == 0 && self.failed == 0
+    }
+}
+
+/// Expands files, directories (walked recursively for `extensions`) and glob
+/// patterns into the files to transpile, without duplicates.
+pub fn collect_inputs(inputs: &[String], extensions: &[String]) -> Result<Vec<SourceFile>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            walk(path, path, extensions, &mut files)?;
+        } else if path.is_file() {
+            let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            files.push(SourceFile { path: path.to_path_buf(), root });
+        } else if input.contains(['*', '?', '[']) {
+            let root = glob_root(input);
+            let paths = glob::glob(input).map_err(|e| invalid(input, e.to_string()))?;
+            for entry in paths {
+                let path = entry.map_err(|e| invalid(input, e.to_string()))?;
+                if path.is_file() {
+                    files.push(SourceFile { path, root: root.clone() });
+                }
+            }
+        } else {
+            return Err(invalid(input, "No such file or directory".to_string()));
+        }
+    }
+    let mut seen = ahash::AHashSet::new();
+    files.retain(|f| seen.insert(f.path.clone()));
+    Ok(files)
+}
+
+fn invalid(path: &str, message: String) -> Error {
+    Error::Path { path: path.to_string(), message }
+}
+
+fn walk(dir: &Path, root: &Path, extensions: &[String], files: &mut Vec<SourceFile>) -> Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| invalid(&dir.display().to_string(), format!("Cannot read directory: {}", e)))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            walk(&path, root, extensions, files)?;
+        } else if path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| extensions.iter().any(|x| x == e))
+        {
+            files.push(SourceFile { path, root: root.to_path_buf() });
+        }
+    }
+    Ok(())
+}
+
+// The leading components of a glob pattern that contain no wildcard.
+pub(crate) fn glob_root(pattern: &str) -> PathBuf {
+    let mut root = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if let Component::Normal(part) = component
+            && part.to_string_lossy().contains(['*', '?', '['])
+        {
+            break;
+        }
+        root.push(component);
+    }
+    root
+}
+
+/// Where the output for `file` goes under `options`.
+pub fn output_path(file: &SourceFile, options: &BatchOptions) -> PathBuf {
+    let relative = file.path.strip_prefix(&file.root).unwrap_or(&file.path);
+    let path = match &options.out_dir {
+        Some(dir) => dir.join(relative),
+        None => file.path.clone(),
+    };
+    path.with_extension(&options.extension)
+}
+
+/// Transpiles `files` with `table` on `options.jobs` threads. Results are in
+/// the order of `files`; only a bad configuration fails the whole run. Files
+/// whose output paths collide all fail without being written.
+pub fn run(files: &[SourceFile], table: &MappingTable, threat_detector: &ThreatDetector, options: &BatchOptions) -> Result<Vec<FileResult>> {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.set_engine(options.engine)?;
+    transpiler.set_normalization(options.normalization)?;
+    transpiler.set_confusables(options.confusables.clone())?;
+    transpiler.configure_table(table.clone())?;
+    transpiler.set_output_scan(options.output_scan);
+    let cache = match &options.cache_dir {
+        Some(dir) if !options.source_maps => {
+            let fingerprint = cache::fingerprint(table, options.normalization, &options.confusables, threat_detector, options.output_scan, options.bypass);
+            Some(Cache::open(dir, fingerprint)?)
+        },
+        _ => None,
+    };
+
+    let mut claims: AHashMap<PathBuf, Vec<&Path>> = AHashMap::new();
+    for file in files {
+        claims.entry(output_path(file, options)).or_default().push(&file.path);
+    }
+    let clash = |file: &SourceFile| {
+        let output = output_path(file, options);
+        let others: Vec<String> = claims[&output].iter()
+            .filter(|&&path| path != file.path)
+            .map(|path| path.display().to_string())
+            .collect();
+        (!others.is_empty()).then(|| (output, format!("Output is also written by {}", others.join(", "))))
+    };
+
+    let jobs = match options.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, FileResult)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(files.len()))
+            .map(|_| scope.spawn(|| {
+                let mut transpiler = transpiler.clone();
+                let mut done = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else {
+                        break;
+                    };
+                    let result = match clash(file) {
+                        Some((output, message)) => FileResult {
+                            status: FileStatus::Failed(message),
+                            ..FileResult::new(file, output)
+                        },
+                        None => process(&mut transpiler, file, threat_detector, options, cache.as_ref()),
+                    };
+                    done.push((index, result));
+                }
+                done
+            }))
+            .collect();
+        workers.into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+fn process(transpiler: &mut SymbolTranspiler, file: &SourceFile, threat_detector: &ThreatDetector, options: &BatchOptions, cache: Option<&Cache>) -> FileResult {
+    let output = output_path(file, options);
+    let mut result = FileResult::new(file, output.clone());
+    if output == file.path {
+        result.status = FileStatus::Failed("Output would overwrite the source".to_string());
+        return result;
+    }
+    result.source = match std::fs::read_to_string(&file.path) {
+        Ok(source) => match transpiler.normalize(&source) {
+            Cow::Owned(normalized) => normalized,
+            Cow::Borrowed(_) => source,
+        },
+        Err(e) => {
+            result.status = FileStatus::Failed(format!("Cannot read file: {}", e));
+            return result;
+        },
+    };
+    let source = &result.source;
+
+    result.dialect = options.dialect.as_ref().and_then(|d| d.check(source));
+    if options.strict_dialect && let Some(mismatch) = &result.dialect {
+        result.status = FileStatus::Failed(mismatch.to_string());
+        return result;
+    }
+
+    let hit = cache.and_then(|cache| cache.get(source));
+    result.cached = cache.map(|_| hit.is_some());
+    let (code, map) = match hit {
+        Some(code) => (code, None),
+        None => {
+            let transpiled = if options.source_maps {
+                transpiler.transpile_with_map(source, threat_detector, options.bypass).map(|(code, map)| (code, Some(map)))
+            } else {
+                transpiler.transpile(source, threat_detector, options.bypass).map(|code| (code, None))
+            };
+            let (code, map) = match transpiled {
+                Ok(transpiled) => transpiled,
+                Err(Error::Security(violations)) => {
+                    result.status = FileStatus::Blocked(violations);
+                    return result;
+                },
+                Err(e) => {
+                    result.status = FileStatus::Failed(e.to_string());
+                    return result;
+                },
+            };
+            result.warnings = transpiler.take_warnings();
+            result.exemptions = transpiler.take_exemptions();
+            result.confusables = transpiler.take_confusables();
+            if let Some(cache) = cache
+                && result.warnings.is_empty()
+                && result.exemptions.is_empty()
+                && result.confusables.is_empty()
+            {
+                cache.put(source, &code);
+            }
+            (code, map)
+        },
+    };
+
+    if std::fs::read_to_string(&output).is_ok_and(|existing| existing == code) && !options.source_maps {
+        result.status = FileStatus::Unchanged;
+        return result;
+    }
+    let written = output.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&output, &code))
+        .and_then(|_| match map {
+            Some(map) => {
+                let name = |p: &Path| p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let mut map_path = output.clone().into_os_string();
+                map_path.push(".map");
+                std::fs::write(map_path, map.to_v3_json(source, &code, &name(&output), &file.path.display().to_string()))
+            },
+            None => Ok(()),
+        });
+    if let Err(e) = written {
+        result.status = FileStatus::Failed(format!("Cannot write {}: {}", output.display(), e));
+    }
+    result
+}
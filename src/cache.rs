@@ -0,0 +1,127 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use crate::mapping_file::MappingTable;
+use crate::normalize::{Confusables, Normalization};
+use crate::threat_detector::ThreatDetector;
+use crate::transpiler::OutputScan;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use xxhash_rust::xxh3::xxh3_128;
+
+/// On-disk store of transpiled outputs, keyed by a hash of the source and of
+/// everything that affects its transpilation.
+///
+/// Only clean results are stored: outputs that raised no warning, used no
+/// trusted exemption and matched no confusable, so a hit never hides a
+/// diagnostic. Entries are never evicted; deleting the directory empties the
+/// cache.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    fingerprint: String,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Lookups made against a [`Cache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStats {
+    /// Hits as a fraction of all lookups; 0 when there were none.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl Cache {
+    /// Opens the cache in `dir` for one configuration; `fingerprint` comes from
+    /// [`fingerprint`] and separates entries of different configurations.
+    pub fn open(dir: &Path, fingerprint: String) -> Result<Self> {
+        std::fs::create_dir_all(dir).map_err(|e| Error::Path {
+            path: dir.display().to_string(),
+            message: format!("Cannot create cache directory: {}", e),
+        })?;
+        Ok(Self { dir: dir.to_path_buf(), fingerprint, hits: AtomicUsize::new(0), misses: AtomicUsize::new(0) })
+    }
+
+    fn entry(&self, source: &str) -> PathBuf {
+        let mut keyed = String::with_capacity(self.fingerprint.len() + source.len() + 1);
+        keyed.push_str(&self.fingerprint);
+        keyed.push('\0');
+        keyed.push_str(source);
+        let key = format!("{:032x}", xxh3_128(keyed.as_bytes()));
+        self.dir.join(&key[..2]).join(&key[2..])
+    }
+
+    /// The cached output for `source`, counting the lookup as a hit or miss.
+    pub fn get(&self, source: &str) -> Option<String> {
+        match std::fs::read_to_string(self.entry(source)) {
+            Ok(output) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(output)
+            },
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+
+    /// Stores `output` for `source`. Failures only cost a future miss, so they
+    /// are ignored.
+    pub fn put(&self, source: &str, output: &str) {
+        let path = self.entry(source);
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        let _ = path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&temporary, output))
+            .and_then(|_| std::fs::rename(&temporary, &path));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits.load(Ordering::Relaxed), misses: self.misses.load(Ordering::Relaxed) }
+    }
+}
+
+/// Describes every input of a transpilation other than the source: mappings,
+/// trusted and infix symbols, normalization, confusables, threat rules, import
+/// policy, detector backend, output scan mode, bypass and the crate version.
+pub fn fingerprint(
+    table: &MappingTable,
+    normalization: Normalization,
+    confusables: &Confusables,
+    threat_detector: &ThreatDetector,
+    output_scan: OutputScan,
+    bypass: bool,
+) -> String {
+    let mut text = format!("{}\n{:?} {:?} {:?} {}\n", env!("CARGO_PKG_VERSION"), normalization, threat_detector.backend(), output_scan, bypass);
+
+    let mut mappings: Vec<_> = table.mappings.iter().collect();
+    mappings.sort();
+    for (symbol, replacement) in mappings {
+        let trusted = if table.trusted.contains(symbol) { "trusted" } else { "" };
+        let infix = if table.infix.contains(symbol) { "infix" } else { "" };
+        let _ = writeln!(text, "map {:?} {:?} {} {}", symbol, replacement, trusted, infix);
+    }
+    let mut confusables: Vec<_> = confusables.map.iter().collect();
+    confusables.sort();
+    for (found, symbol) in confusables {
+        let _ = writeln!(text, "confusable {:?} {:?}", found, symbol);
+    }
+    for rule in threat_detector.rules() {
+        let _ = writeln!(text, "rule {:?} {:?} {}", rule.pattern, rule.category, rule.severity);
+    }
+    let _ = writeln!(text, "imports {:?}", threat_detector.import_policy());
+    text
+}
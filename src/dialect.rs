@@ -0,0 +1,409 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use crate::lexer::{self, SegmentKind};
+use crate::mapping_file::{self, MappingTable};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+
+/// A dialect manifest shipped inside the binary, selectable by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub name: &'static str,
+    source: &'static str,
+}
+
+const BUILTIN: &[Dialect] = &[
+    Dialect { name: "phicode", source: include_str!("dialects/phicode.toml") },
+];
+
+impl Dialect {
+    pub fn manifest(&self) -> Result<DialectManifest> {
+        DialectManifest::parse(self.source, "toml", &format!("dialect `{}`", self.name))
+    }
+
+    /// The dialect's mappings, ready to be merged with user overrides.
+    pub fn table(&self) -> Result<MappingTable> {
+        Ok(self.manifest()?.table)
+    }
+}
+
+/// Every built-in dialect.
+pub fn builtin() -> &'static [Dialect] {
+    BUILTIN
+}
+
+/// Looks up a built-in dialect by name.
+pub fn find(name: &str) -> Result<&'static Dialect> {
+    BUILTIN.iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| Error::UnknownDialect {
+            name: name.to_string(),
+            available: BUILTIN.iter().map(|d| d.name.to_string()).collect(),
+        })
+}
+
+/// Resolves `name` to a built-in dialect, or else reads it as a manifest path.
+pub fn resolve(name: &str) -> Result<DialectManifest> {
+    match find(name) {
+        Ok(dialect) => dialect.manifest(),
+        Err(_) if Path::new(name).is_file() => DialectManifest::load(Path::new(name)),
+        Err(e) => Err(e),
+    }
+}
+
+/// A dotted numeric version such as `1.2.0`; missing parts count as zero.
+#[derive(Debug, Clone)]
+pub struct Version {
+    text: String,
+    parts: Vec<u64>,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let parts = text.split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Self { text: text.to_string(), parts })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.parts.len().max(other.parts.len());
+        let part = |v: &Self, i: usize| v.parts.get(i).copied().unwrap_or(0);
+        (0..len).map(|i| part(self, i).cmp(&part(other, i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// A symbol added, removed or remapped in some version of a dialect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolChange {
+    pub version: Version,
+    pub symbol: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Why the symbol changed, for changes in how it is transpiled.
+    pub note: Option<String>,
+}
+
+impl fmt::Display for SymbolChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => write!(f, "`{}` changed from `{}` to `{}`", self.symbol, from, to)?,
+            (None, Some(to)) => write!(f, "`{}` added as `{}`", self.symbol, to)?,
+            (Some(from), None) => write!(f, "`{}` (was `{}`) removed", self.symbol, from)?,
+            (None, None) => write!(f, "`{}` changed", self.symbol)?,
+        }
+        write!(f, " in {}", self.version)?;
+        match &self.note {
+            Some(note) => write!(f, " ({})", note),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A named, versioned symbol table with the history of its changes:
+///
+/// ```toml
+/// name = "phicode"
+/// version = "1.1.0"
+/// description = "..."       # optional
+///
+/// [symbols]                 # same format as a mapping file
+/// "ƒ" = "def"
+///
+/// [[changes]]
+/// version = "1.1.0"         # the version the change shipped in
+/// symbol = "π"
+/// from = "print"            # omitted for a new symbol
+/// to = "log"                # omitted for a removed symbol
+/// note = "..."              # optional, shown with the change
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialectManifest {
+    pub name: String,
+    pub version: Version,
+    pub description: String,
+    pub table: MappingTable,
+    pub changes: Vec<SymbolChange>,
+}
+
+impl DialectManifest {
+    pub fn parse(text: &str, format: &str, origin: &str) -> Result<Self> {
+        Self::from_document(&mapping_file::parse_document(text, format, origin)?, origin)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_document(&mapping_file::read_document(path)?, &path.display().to_string())
+    }
+
+    /// Interprets a parsed manifest; `origin` names it in errors.
+    pub fn from_document(document: &Value, origin: &str) -> Result<Self> {
+        let invalid = |message: String| Error::Document { origin: origin.to_string(), message };
+        let string = |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let version = |value: &Value| string(value, "version")
+            .and_then(|v| Version::parse(&v))
+            .ok_or_else(|| invalid("`version` must be a dotted number such as \"1.0.0\"".to_string()));
+
+        let name = string(document, "name")
+            .ok_or_else(|| invalid("Dialect needs a `name`".to_string()))?;
+        let symbols = document.get("symbols")
+            .ok_or_else(|| invalid("Dialect needs a `symbols` table".to_string()))?;
+        let mut changes = Vec::new();
+        if let Some(entries) = document.get("changes") {
+            let entries = entries.as_array()
+                .ok_or_else(|| invalid("`changes` must be a list".to_string()))?;
+            for entry in entries {
+                changes.push(SymbolChange {
+                    version: version(entry)?,
+                    symbol: string(entry, "symbol")
+                        .ok_or_else(|| invalid("Every change needs a `symbol`".to_string()))?,
+                    from: string(entry, "from"),
+                    to: string(entry, "to"),
+                    note: string(entry, "note"),
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.version.cmp(&b.version));
+
+        Ok(Self {
+            version: version(document)?,
+            description: string(document, "description").unwrap_or_default(),
+            table: MappingTable::from_document(symbols, origin)?,
+            changes,
+            name,
+        })
+    }
+
+    /// Compares the dialect a source declares against this manifest, if it
+    /// declares one. A source targeting an older version only mismatches if
+    /// its code uses a symbol changed since. `None` means it is compatible.
+    pub fn check(&self, source: &str) -> Option<Mismatch> {
+        let mut mismatch = self.compare(DialectHeader::find(source)?)?;
+        if mismatch.declared.name == self.name && mismatch.declared.version < self.version {
+            let code: Vec<&str> = lexer::segments(source).into_iter()
+                .filter(|segment| segment.kind == SegmentKind::Code)
+                .map(|segment| &source[segment.range])
+                .collect();
+            mismatch.changes.retain(|change| code.iter().any(|text| text.contains(change.symbol.as_str())));
+            if mismatch.changes.is_empty() {
+                return None;
+            }
+        }
+        Some(mismatch)
+    }
+
+    /// Compares a declared dialect against this manifest without looking at
+    /// the source, so every change since an older declared version counts.
+    /// `None` means the versions are compatible.
+    pub fn compare(&self, declared: DialectHeader) -> Option<Mismatch> {
+        if declared.name != self.name {
+            return Some(Mismatch { declared, dialect: self.name.clone(), version: self.version.clone(), changes: Vec::new() });
+        }
+        let changes: Vec<SymbolChange> = match declared.version.cmp(&self.version) {
+            Ordering::Equal => return None,
+            Ordering::Less => self.changes.iter()
+                .filter(|c| c.version > declared.version && c.version <= self.version)
+                .cloned()
+                .collect(),
+            // Changes after our version are unknown to us.
+            Ordering::Greater => Vec::new(),
+        };
+        if declared.version < self.version && changes.is_empty() {
+            return None;
+        }
+        Some(Mismatch { declared, dialect: self.name.clone(), version: self.version.clone(), changes })
+    }
+}
+
+/// The `# dialect: <name> <version>` comment a source may start with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialectHeader {
+    pub name: String,
+    pub version: Version,
+    pub line: usize,
+}
+
+impl DialectHeader {
+    /// Looks for the header among the comments and blank lines opening `source`.
+    pub fn find(source: &str) -> Option<Self> {
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let comment = line.strip_prefix('#')?.trim();
+            if let Some(rest) = comment.strip_prefix("dialect:") {
+                let mut words = rest.split_whitespace();
+                let name = words.next()?.to_string();
+                let version = Version::parse(words.next()?)?;
+                return Some(Self { name, version, line: index + 1 });
+            }
+        }
+        None
+    }
+}
+
+/// A source declaring a dialect version other than the one configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub declared: DialectHeader,
+    pub dialect: String,
+    pub version: Version,
+    /// Symbol changes between the declared and configured versions, when the
+    /// source targets an older version of the same dialect.
+    pub changes: Vec<SymbolChange>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let declared = &self.declared;
+        write!(f, "source targets {} {}", declared.name, declared.version)?;
+        if declared.name != self.dialect {
+            return write!(f, ", but dialect {} {} is configured", self.dialect, self.version);
+        }
+        if declared.version > self.version {
+            return write!(f, ", which is newer than the configured {}; its changes are unknown", self.version);
+        }
+        write!(f, ", but {} is configured; changed symbols: ", self.version)?;
+        let changes: Vec<String> = self.changes.iter().map(|c| c.to_string()).collect();
+        f.write_str(&changes.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+name = "demo"
+version = "2.0.0"
+
+[symbols]
+"ƒ" = "def"
+"π" = "log"
+"∈" = { replacement = "in", infix = true }
+
+[[changes]]
+version = "2.0.0"
+symbol = "π"
+from = "print"
+to = "log"
+
+[[changes]]
+version = "1.1.0"
+symbol = "∈"
+note = "spaced from identifiers"
+"#;
+
+    fn manifest() -> DialectManifest {
+        DialectManifest::parse(MANIFEST, "toml", "demo").unwrap()
+    }
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    fn changed(mismatch: &Mismatch) -> Vec<&str> {
+        mismatch.changes.iter().map(|c| c.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(version("1.0"), version("1.0.0"));
+        assert!(version("1.10") > version("1.9.3"));
+        assert!(version("2") > version("1.99"));
+        assert_eq!(version("1.0").to_string(), "1.0");
+        for bad in ["", "1.x", "1..0", "v1", "-1"] {
+            assert!(Version::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn headers_sit_among_leading_comments() {
+        let header = DialectHeader::find("#!/usr/bin/env python\n\n#  dialect:  demo 1.2\nƒ f(): pass\n").unwrap();
+        assert_eq!((header.name.as_str(), header.version, header.line), ("demo", version("1.2"), 3));
+        for source in [
+            "ƒ f(): pass\n# dialect: demo 1.0\n",
+            "# dialect: demo\n",
+            "# dialect: demo one\n",
+            "'# dialect: demo 1.0'\n",
+            "",
+        ] {
+            assert_eq!(DialectHeader::find(source), None, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn manifests_list_changes_in_version_order() {
+        let manifest = manifest();
+        assert_eq!((manifest.name.as_str(), manifest.table.mappings.len()), ("demo", 3));
+        let changes: Vec<String> = manifest.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(changes, ["`∈` changed in 1.1.0 (spaced from identifiers)", "`π` changed from `print` to `log` in 2.0.0"]);
+    }
+
+    #[test]
+    fn only_used_changes_mismatch() {
+        let manifest = manifest();
+        assert_eq!(manifest.check("# dialect: demo 2.0\nπ(x ∈ s)\n"), None);
+        assert_eq!(manifest.check("ƒ f(): π(1)\n"), None);
+
+        // Changes after the declared version that the code uses.
+        let mismatch = manifest.check("# dialect: demo 1.0.0\nƒ f(): π(x ∈ s)\n").unwrap();
+        assert_eq!(changed(&mismatch), ["∈", "π"]);
+        let mismatch = manifest.check("# dialect: demo 1.1.0\nƒ f(): π(x ∈ s)\n").unwrap();
+        assert_eq!(changed(&mismatch), ["π"]);
+        assert_eq!(mismatch.declared.line, 1);
+
+        // Symbols in strings and comments, or untouched since, do not count.
+        assert_eq!(manifest.check("# dialect: demo 1.0.0\nƒ f(): '∈'  # π\n"), None);
+    }
+
+    #[test]
+    fn other_dialects_and_newer_versions_always_mismatch() {
+        let manifest = manifest();
+        let other = manifest.check("# dialect: other 2.0.0\nƒ f(): pass\n").unwrap();
+        assert_eq!(other.to_string(), "source targets other 2.0.0, but dialect demo 2.0.0 is configured");
+        let newer = manifest.check("# dialect: demo 3.0\nƒ f(): pass\n").unwrap();
+        assert_eq!(newer.to_string(), "source targets demo 3.0, which is newer than the configured 2.0.0; its changes are unknown");
+    }
+
+    #[test]
+    fn builtin_changes_explain_themselves() {
+        let mismatch = resolve("phicode").unwrap().check("# dialect: phicode 1.0.0\nok = a∈b\n").unwrap();
+        assert_eq!(mismatch.to_string(), "source targets phicode 1.0.0, but 1.1.0 is configured; changed symbols: \
+            `∈` changed in 1.1.0 (spaced from identifiers it touches, so `a∈b` is now `a in b`)");
+    }
+
+    #[test]
+    fn comparing_headers_counts_every_change() {
+        let declared = DialectHeader::find("# dialect: demo 1.0.0\n").unwrap();
+        assert_eq!(changed(&manifest().compare(declared).unwrap()), ["∈", "π"]);
+    }
+}
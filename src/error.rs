@@ -0,0 +1,223 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::policy::{Severity, ThreatRule};
+use crate::validation::MappingIssue;
+use std::fmt;
+
+/// Errors produced while configuring or running the transpiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The symbol matcher could not be built by the selected engine.
+    Pattern(String),
+    /// The threat detector could not be built.
+    ThreatDetector(String),
+    /// A mapping or policy document could not be read, parsed or interpreted.
+    Document { origin: String, message: String },
+    /// An operation needs configuration that has not been done yet.
+    NotConfigured(&'static str),
+    /// Mappings refused by validation; only error-level issues. Never empty
+    /// when returned by this crate.
+    Mappings(Vec<MappingIssue>),
+    /// Replacements flagged by the threat detector, in source order. Never
+    /// empty when returned by this crate.
+    Security(Vec<SecurityViolation>),
+    /// A streamed input could not be read or decoded, or the output written.
+    Io(String),
+    /// A file or directory could not be found, read or created.
+    Path { path: String, message: String },
+    /// The file watcher could not be started.
+    Watch(String),
+    /// No built-in dialect has the name.
+    UnknownDialect { name: String, available: Vec<String> },
+    /// The operation cannot honour the configured security checks.
+    Unsupported(&'static str),
+}
+
+/// Which check produced a [`SecurityViolation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// A mapping's replacement text was dangerous.
+    Replacement,
+    /// The scan over the whole transpiled output found dangerous code.
+    Output,
+    /// The transpiled output imports a module the import policy refuses.
+    Import,
+}
+
+/// Dangerous code the threat detector refused, and where it was found. For
+/// output findings `symbol` is the source text the match was generated from
+/// and `replacement` the matched output text; for imports `replacement` is the
+/// refused module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityViolation {
+    pub stage: Stage,
+    pub symbol: String,
+    pub replacement: String,
+    pub pattern: String,
+    pub category: String,
+    pub severity: Severity,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SecurityViolation {
+    pub(crate) fn new(stage: Stage, symbol: String, replacement: String, rule: &ThreatRule, offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            stage,
+            symbol,
+            replacement,
+            pattern: rule.pattern.clone(),
+            category: rule.category.clone(),
+            severity: rule.severity,
+            offset,
+            line,
+            column,
+        }
+    }
+
+    /// One-line description of the finding, without its location.
+    pub fn message(&self) -> String {
+        match self.stage {
+            Stage::Replacement => format!(
+                "`{}` expands to `{}`, matching {} threat pattern `{}`",
+                self.symbol, self.replacement, self.category, self.pattern,
+            ),
+            Stage::Output => format!(
+                "transpiled code `{}` matches {} threat pattern `{}`",
+                self.replacement, self.category, self.pattern,
+            ),
+            Stage::Import if self.pattern.is_empty() => format!(
+                "import of `{}` is not in the import allow list", self.replacement,
+            ),
+            Stage::Import => format!(
+                "import of `{}` is denied by import pattern `{}`", self.replacement, self.pattern,
+            ),
+        }
+    }
+
+    /// Renders a compiler-style diagnostic pointing at the symbol in `source`;
+    /// `level` is the leading label, e.g. `error` or `warning`.
+    pub fn render(&self, source: &str, name: &str, level: &str) -> String {
+        let line_text = source.lines().nth(self.line - 1).unwrap_or("");
+        let gutter = self.line.to_string().len();
+        let first_line = self.symbol.lines().next().unwrap_or("");
+        let underline = "^".repeat(first_line.chars().count().max(1));
+        let title = match self.stage {
+            Stage::Replacement => format!("dangerous replacement for symbol `{}`", self.symbol),
+            Stage::Output => "dangerous code in transpiled output".to_string(),
+            Stage::Import => format!("disallowed import of `{}`", self.replacement),
+        };
+        format!(
+            "{level}: {title}\n\
+             {pad}--> {name}:{line}:{column}\n\
+             {pad} |\n\
+             {line:>gutter$} | {line_text}\n\
+             {pad} | {indent}{underline} {message}\n",
+            message = self.message(),
+            pad = " ".repeat(gutter),
+            line = self.line,
+            column = self.column,
+            indent = " ".repeat(self.column - 1),
+        )
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pattern(message) => write!(f, "Symbol matcher build failed: {}", message),
+            Error::ThreatDetector(message) => write!(f, "Threat detector: {}", message),
+            Error::Document { origin, message } => write!(f, "{}: {}", origin, message),
+            Error::NotConfigured(what) => write!(f, "{} not configured", what),
+            Error::Mappings(issues) => {
+                let Some(issue) = issues.first() else {
+                    return write!(f, "Invalid mappings");
+                };
+                write!(f, "Invalid mappings: {}", issue)?;
+                if issues.len() > 1 {
+                    write!(f, " (and {} more)", issues.len() - 1)?;
+                }
+                Ok(())
+            },
+            Error::Security(violations) => {
+                let Some(v) = violations.first() else {
+                    return write!(f, "Security: code blocked");
+                };
+                write!(f, "Security: {} at {}:{}", v.message(), v.line, v.column)?;
+                if violations.len() > 1 {
+                    write!(f, " (and {} more)", violations.len() - 1)?;
+                }
+                Ok(())
+            },
+            Error::Io(message) => write!(f, "I/O error: {}", message),
+            Error::Path { path, message } => write!(f, "{}: {}", path, message),
+            Error::Watch(message) => write!(f, "File watcher: {}", message),
+            Error::UnknownDialect { name, available } => {
+                write!(f, "Unknown dialect `{}` (available: {})", name, available.join(", "))
+            },
+            Error::Unsupported(what) => write!(f, "{} is not supported", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_security_errors_display() {
+        assert_eq!(Error::Security(Vec::new()).to_string(), "Security: code blocked");
+    }
+
+    fn violation(stage: Stage, symbol: &str, replacement: &str, line: usize, column: usize) -> SecurityViolation {
+        let rule = ThreatRule { pattern: "eval(".to_string(), category: "code-execution".to_string(), severity: Severity::Critical };
+        SecurityViolation::new(stage, symbol.to_string(), replacement.to_string(), &rule, 0, line, column)
+    }
+
+    #[test]
+    fn carets_point_at_the_symbol() {
+        // `𝑓` is four bytes and two UTF-16 units but one column.
+        let source = "x = 1\n𝑓 = ∂1)\n";
+        let rendered = violation(Stage::Replacement, "∂", "eval(", 2, 5).render(source, "a.φ", "error");
+        assert_eq!(rendered, "\
+error: dangerous replacement for symbol `∂`
+ --> a.φ:2:5
+  |
+2 | 𝑓 = ∂1)
+  |     ^ `∂` expands to `eval(`, matching code-execution threat pattern `eval(`
+");
+    }
+
+    #[test]
+    fn carets_span_the_first_line_of_output_findings() {
+        let source = "é = [\n    1]\n".repeat(5) + "\u{3bb}x = eval(\n1)\n";
+        let rendered = violation(Stage::Output, "eval(\n1", "eval(", 11, 6).render(&source, "a.φ", "warning");
+        assert_eq!(rendered, "\
+warning: dangerous code in transpiled output
+  --> a.φ:11:6
+   |
+11 | λx = eval(
+   |      ^^^^^ transpiled code `eval(` matches code-execution threat pattern `eval(`
+");
+    }
+
+    #[test]
+    fn failures_name_their_cause() {
+        let dialect = Error::UnknownDialect { name: "phi".to_string(), available: vec!["phicode".to_string()] };
+        assert_eq!(dialect.to_string(), "Unknown dialect `phi` (available: phicode)");
+        let path = Error::Path { path: "src".to_string(), message: "No such file or directory".to_string() };
+        assert_eq!(path.to_string(), "src: No such file or directory");
+        assert_eq!(Error::Pattern("too big".to_string()).to_string(), "Symbol matcher build failed: too big");
+    }
+
+    #[test]
+    fn empty_mapping_errors_display() {
+        assert_eq!(Error::Mappings(Vec::new()).to_string(), "Invalid mappings");
+    }
+}
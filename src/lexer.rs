@@ -0,0 +1,353 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::matcher::SymbolMatcher;
+use std::ops::Range;
+
+/// Whether a segment is code, or a string literal / comment left untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Code,
+    Literal,
+}
+
+/// A byte range of the source with its kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub range: Range<usize>,
+}
+
+/// Splits source into code and literal (string/comment) segments covering the
+/// whole input. F-string replacement fields are reported as code.
+pub fn segments(source: &str) -> Vec<Segment> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_code(false);
+
+    let mut segments = Vec::with_capacity(scanner.literals.len() * 2 + 1);
+    let mut cursor = 0;
+    for literal in scanner.literals {
+        if literal.start > cursor {
+            segments.push(Segment { kind: SegmentKind::Code, range: cursor..literal.start });
+        }
+        cursor = literal.end;
+        segments.push(Segment { kind: SegmentKind::Literal, range: literal });
+    }
+    if cursor < source.len() {
+        segments.push(Segment { kind: SegmentKind::Code, range: cursor..source.len() });
+    }
+    segments
+}
+
+/// The offset just past the last line break of `source` that lies in code
+/// outside any string, comment, bracket or line continuation. The text before
+/// it transpiles the same whatever follows.
+pub fn last_statement_break(source: &str) -> Option<usize> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_code(false);
+    scanner.last_break
+}
+
+/// A match in the source and the range its replacement occupies in the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    pub source: Range<usize>,
+    pub output: Range<usize>,
+}
+
+/// Applies `matcher` to the code segments of `source` only, recording where each
+/// match came from and where its replacement landed in the output. `replace`
+/// gets each match and its range in `source`.
+pub fn replace_in_code<F>(matcher: &SymbolMatcher, source: &str, mut replace: F) -> (String, Vec<Replacement>)
+where
+    F: FnMut(&str, Range<usize>) -> String,
+{
+    let mut output = String::with_capacity(source.len());
+    let mut replacements = Vec::new();
+    for segment in segments(source) {
+        let text = &source[segment.range.clone()];
+        if segment.kind == SegmentKind::Literal {
+            output.push_str(text);
+            continue;
+        }
+
+        let mut last = 0;
+        for matched in matcher.find_iter(text) {
+            output.push_str(&text[last..matched.start]);
+            let range = segment.range.start + matched.start..segment.range.start + matched.end;
+            let replacement = replace(&text[matched.clone()], range.clone());
+            let start = output.len();
+            output.push_str(&replacement);
+            replacements.push(Replacement { source: range, output: start..output.len() });
+            last = matched.end;
+        }
+        output.push_str(&text[last..]);
+    }
+    (output, replacements)
+}
+
+/// Maps an output byte offset back to the source; offsets inside a
+/// replacement map to the start of the symbol it replaced.
+pub fn source_offset(replacements: &[Replacement], offset: usize) -> usize {
+    let index = replacements.partition_point(|r| r.output.start <= offset);
+    if index == 0 {
+        return offset;
+    }
+    let r = &replacements[index - 1];
+    if offset < r.output.end {
+        r.source.start
+    } else {
+        r.source.end + (offset - r.output.end)
+    }
+}
+
+/// One-based line and character column of a byte offset.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    literals: Vec<Range<usize>>,
+    last_break: Option<usize>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { bytes: source.as_bytes(), pos: 0, literals: Vec::new(), last_break: None }
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn mark_literal(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        match self.literals.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => self.literals.push(start..end),
+        }
+    }
+
+    // Scans code until EOF, or in a replacement field until the closing `}`
+    // or the start of a conversion / format spec at bracket depth zero.
+    fn scan_code(&mut self, in_field: bool) {
+        let mut depth = 0usize;
+        while let Some(byte) = self.peek(0) {
+            match byte {
+                b'#' => {
+                    let start = self.pos;
+                    while let Some(b) = self.peek(0) {
+                        if b == b'\n' || b == b'\r' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                    self.mark_literal(start, self.pos);
+                }
+                b'\'' | b'"' => self.scan_string(),
+                b'\n' if !in_field && depth == 0 => {
+                    let line = &self.bytes[..self.pos];
+                    if !line.strip_suffix(b"\r").unwrap_or(line).ends_with(b"\\") {
+                        self.last_break = Some(self.pos + 1);
+                    }
+                    self.pos += 1;
+                }
+                b'(' | b'[' | b'{' => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                b')' | b']' | b'}' if depth > 0 => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                b'}' if in_field => return,
+                b':' if in_field && depth == 0 => return,
+                b'!' if in_field && depth == 0 && self.peek(1) != Some(b'=') => return,
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn string_prefix_start(&self) -> Option<usize> {
+        let mut start = self.pos;
+        while start > 0 && is_identifier_byte(self.bytes[start - 1]) {
+            start -= 1;
+        }
+        let prefix = &self.bytes[start..self.pos];
+        if prefix.len() > 2 {
+            return None;
+        }
+        let lowered: Vec<u8> = prefix.iter().map(|b| b.to_ascii_lowercase()).collect();
+        match lowered.as_slice() {
+            b"" | b"r" | b"u" | b"b" | b"f" | b"t" | b"br" | b"rb" | b"fr" | b"rf" | b"tr" | b"rt" => Some(start),
+            _ => None,
+        }
+    }
+
+    fn scan_string(&mut self) {
+        let quote = self.bytes[self.pos];
+        let start = self.string_prefix_start().unwrap_or(self.pos);
+        let formatted = self.bytes[start..self.pos]
+            .iter()
+            .any(|b| matches!(b.to_ascii_lowercase(), b'f' | b't'));
+        let triple = self.peek(1) == Some(quote) && self.peek(2) == Some(quote);
+        self.pos += if triple { 3 } else { 1 };
+
+        let mut literal_start = start;
+        while let Some(byte) = self.peek(0) {
+            match byte {
+                b'\\' => self.pos = (self.pos + 2).min(self.bytes.len()),
+                b'\n' | b'\r' if !triple => break,
+                b if b == quote => {
+                    if !triple {
+                        self.pos += 1;
+                        break;
+                    }
+                    if self.peek(1) == Some(quote) && self.peek(2) == Some(quote) {
+                        self.pos += 3;
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                b'{' | b'}' if formatted && self.peek(1) == Some(byte) => self.pos += 2,
+                b'{' if formatted => {
+                    self.pos += 1;
+                    self.mark_literal(literal_start, self.pos);
+                    self.scan_field();
+                    literal_start = self.pos;
+                }
+                _ => self.pos += 1,
+            }
+        }
+        self.mark_literal(literal_start, self.pos);
+    }
+
+    // Scans a replacement field after its opening `{`, leaving `pos` on the
+    // closing `}`. Format specs are literal but may nest further fields.
+    fn scan_field(&mut self) {
+        self.scan_code(true);
+        let mut spec_start = self.pos;
+        while let Some(byte) = self.peek(0) {
+            match byte {
+                b'}' => break,
+                b'{' => {
+                    self.pos += 1;
+                    self.mark_literal(spec_start, self.pos);
+                    self.scan_field();
+                    spec_start = self.pos;
+                    if self.peek(0) == Some(b'}') {
+                        self.pos += 1;
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+        self.mark_literal(spec_start, self.pos);
+    }
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte >= 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{MatchEngine, Needle};
+    use SegmentKind::{Code, Literal};
+
+    fn split(source: &str) -> Vec<(SegmentKind, &str)> {
+        segments(source).into_iter().map(|s| (s.kind, &source[s.range])).collect()
+    }
+
+    fn replace_x(source: &str) -> String {
+        let matcher = SymbolMatcher::new(vec![Needle::symbol("x")], MatchEngine::Regex).unwrap();
+        replace_in_code(&matcher, source, |_, _| "Y".to_string()).0
+    }
+
+    #[test]
+    fn segments_cover_the_source() {
+        let source = "a = 'b' # c\nd = \"e\"";
+        assert_eq!(split(source), [(Code, "a = "), (Literal, "'b'"), (Code, " "), (Literal, "# c"), (Code, "\nd = "), (Literal, "\"e\"")]);
+        assert_eq!(split(""), []);
+    }
+
+    #[test]
+    fn string_prefixes_belong_to_the_literal() {
+        assert_eq!(split("rb'x' + Rb\"x\""), [(Literal, "rb'x'"), (Code, " + "), (Literal, "Rb\"x\"")]);
+        assert_eq!(split("u'x'"), [(Literal, "u'x'")]);
+        // Not a prefix: the identifier stays code.
+        assert_eq!(split("xr'x'"), [(Code, "xr"), (Literal, "'x'")]);
+        assert_eq!(split("abc'x'"), [(Code, "abc"), (Literal, "'x'")]);
+    }
+
+    #[test]
+    fn escaped_quotes_do_not_end_strings() {
+        assert_eq!(split(r#""a\"b" x"#), [(Literal, r#""a\"b""#), (Code, " x")]);
+        assert_eq!(split(r"'a\\' x"), [(Literal, r"'a\\'"), (Code, " x")]);
+    }
+
+    #[test]
+    fn triple_quoted_strings_span_lines_and_quotes() {
+        let source = "x = '''a\n' '' \"\"\"\nb''' + x";
+        assert_eq!(split(source), [(Code, "x = "), (Literal, "'''a\n' '' \"\"\"\nb'''"), (Code, " + x")]);
+    }
+
+    #[test]
+    fn comments_run_to_the_end_of_the_line() {
+        assert_eq!(split("a # 'b\nc"), [(Code, "a "), (Literal, "# 'b"), (Code, "\nc")]);
+        assert_eq!(split("a # b\r\nc"), [(Code, "a "), (Literal, "# b"), (Code, "\r\nc")]);
+        assert_eq!(split("'#' x"), [(Literal, "'#'"), (Code, " x")]);
+    }
+
+    #[test]
+    fn f_string_fields_are_code() {
+        assert_eq!(split("f'a{x}b'"), [(Literal, "f'a{"), (Code, "x"), (Literal, "}b'")]);
+        assert_eq!(split("f'{x!r}'"), [(Literal, "f'{"), (Code, "x"), (Literal, "!r}'")]);
+        assert_eq!(split("f'{x != y}'"), [(Literal, "f'{"), (Code, "x != y"), (Literal, "}'")]);
+        assert_eq!(split("f'{d[\"k\"]}'"), [(Literal, "f'{"), (Code, "d["), (Literal, "\"k\""), (Code, "]"), (Literal, "}'")]);
+        // Format specs are literal, except for nested fields.
+        assert_eq!(split("f'{x:>{w}}'"), [(Literal, "f'{"), (Code, "x"), (Literal, ":>{"), (Code, "w"), (Literal, "}}'")]);
+        // Only f- and t-strings have fields.
+        assert_eq!(split("'{x}'"), [(Literal, "'{x}'")]);
+        assert_eq!(split("rf'{x}'"), [(Literal, "rf'{"), (Code, "x"), (Literal, "}'")]);
+    }
+
+    #[test]
+    fn doubled_braces_are_escapes() {
+        assert_eq!(split("f'{{x}}'"), [(Literal, "f'{{x}}'")]);
+        assert_eq!(split("f'{{{x}}}'"), [(Literal, "f'{{{"), (Code, "x"), (Literal, "}}}'")]);
+    }
+
+    #[test]
+    fn unterminated_literals_end_safely() {
+        assert_eq!(split("x = 'a\ny"), [(Code, "x = "), (Literal, "'a"), (Code, "\ny")]);
+        assert_eq!(split("x = '''a\ny"), [(Code, "x = "), (Literal, "'''a\ny")]);
+        assert_eq!(split("x = 'a\\"), [(Code, "x = "), (Literal, "'a\\")]);
+        assert_eq!(split("f'{x"), [(Literal, "f'{"), (Code, "x")]);
+    }
+
+    #[test]
+    fn replacements_skip_literals() {
+        assert_eq!(replace_x("x = 'x' # x\nf'{x} x'"), "Y = 'x' # x\nf'{Y} x'");
+        assert_eq!(replace_x("'''x\n''' x"), "'''x\n''' Y");
+    }
+
+    #[test]
+    fn replacements_record_source_and_output_ranges() {
+        let matcher = SymbolMatcher::new(vec![Needle::symbol("λ")], MatchEngine::Regex).unwrap();
+        let (output, replacements) = replace_in_code(&matcher, "λ 'λ' λ", |_, _| "lambda".to_string());
+        assert_eq!(output, "lambda 'λ' lambda");
+        assert_eq!(replacements, [
+            Replacement { source: 0..2, output: 0..6 },
+            Replacement { source: 8..10, output: 12..18 },
+        ]);
+    }
+}
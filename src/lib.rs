@@ -0,0 +1,56 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+//! Symbolic PhiCode to Python transpiler.
+//!
+//! [`SymbolTranspiler`] rewrites symbols to Python using a configured mapping
+//! table, skipping string literals and comments, and consults a
+//! [`ThreatDetector`] before emitting each replacement. The `phicode-transpiler`
+//! binary is a command-line front-end over this crate: it parses options,
+//! reads and writes files and prints what the library reports.
+//!
+//! ```
+//! use ahash::AHashMap;
+//! use phirust_transpiler::{SymbolTranspiler, ThreatDetector};
+//!
+//! let mut mappings = AHashMap::new();
+//! mappings.insert("ƒ".to_string(), "def".to_string());
+//!
+//! let mut transpiler = SymbolTranspiler::new();
+//! transpiler.configure(mappings)?;
+//! let detector = ThreatDetector::new()?;
+//! let python = transpiler.transpile("ƒ main(): pass", &detector, false)?;
+//! assert_eq!(python, "def main(): pass");
+//! # Ok::<(), phirust_transpiler::Error>(())
+//! ```
+mod ast_detector;
+pub mod batch;
+pub mod cache;
+pub mod dialect;
+pub mod error;
+pub mod lexer;
+pub mod mapping_file;
+pub mod matcher;
+pub mod normalize;
+pub mod policy;
+pub mod report;
+pub mod reverse;
+pub mod server;
+pub mod source_map;
+pub mod threat_detector;
+pub mod transpiler;
+pub mod validation;
+pub mod watch;
+
+#[cfg(feature = "python")]
+mod python;
+
+pub use error::{Error, Result, SecurityViolation, Stage};
+pub use matcher::MatchEngine;
+pub use normalize::{Confusable, Confusables, Normalization};
+pub use policy::{ImportAction, ImportPolicy, Severity, ThreatPolicy, ThreatRule};
+pub use reverse::{AmbiguousMapping, RoundTripIssue};
+pub use source_map::{Position, PositionMapping, SourceMap};
+pub use threat_detector::{DetectorBackend, ThreatDetector};
+pub use transpiler::{OutputScan, SymbolTranspiler};
+pub use validation::{IssueKind, IssueLevel, MappingIssue};
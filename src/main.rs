@@ -1,160 +1,569 @@
 // Copyright 2025 Baleine Jay
 // Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
 // Commercial use requires a paid license. See link for details.
-mod threat_detector;
-use threat_detector::ThreatDetector;
-use clap::Parser;
-use std::io::{self, Read, Write};
-use ahash::{AHashMap, AHashSet};
-use regex::{Regex, Captures};
-use serde_json;
-
-pub struct SymbolTranspiler {
-    mappings: AHashMap<String, String>,
-    pattern: Option<Regex>,
-    symbol_bytes: Option<AHashSet<u8>>,
-}
-
-impl SymbolTranspiler {
-    pub fn new() -> Self {
-        Self {
-            mappings: AHashMap::new(),
-            pattern: None,
-            symbol_bytes: None,
-        }
-    }
-
-    pub fn configure(&mut self, mappings: AHashMap<String, String>) -> Result<(), String> {
-        self.mappings = mappings;
-        if self.mappings.is_empty() {
-            self.pattern = None;
-            self.symbol_bytes = None;
-            return Ok(());
-        }
-
-        let mut bytes = AHashSet::new();
-        for symbol in self.mappings.keys() {
-            for byte in symbol.bytes() {
-                if byte > 127 {
-                    bytes.insert(byte);
-                }
+use phirust_transpiler::batch::{self, BatchOptions, FileResult, FileStatus, Summary};
+use phirust_transpiler::dialect::{self, DialectHeader, DialectManifest};
+use phirust_transpiler::mapping_file::{self, MappingLayer, MappingTable};
+use phirust_transpiler::report;
+use phirust_transpiler::server::Server;
+use phirust_transpiler::watch::SourceWatcher;
+use phirust_transpiler::{Confusable, Confusables, DetectorBackend, Error, MatchEngine, Normalization, OutputScan, SecurityViolation, SymbolTranspiler, ThreatDetector, ThreatPolicy};
+use clap::{Parser, ValueEnum};
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "phicode-transpiler")]
+#[command(about = "Fast symbolic transpiler for PhiCode")]
+struct Cli {
+    #[arg(value_name = "INPUT", conflicts_with_all = ["output", "report", "reverse", "serve"], help = "Files, directories or glob patterns to transpile instead of stdin")]
+    inputs: Vec<String>,
+    #[arg(long, value_name = "DIR", help = "Write outputs of INPUTs under DIR, mirroring the source tree (default: next to each source)")]
+    out_dir: Option<PathBuf>,
+    #[arg(long, value_name = "EXT", default_value = "py", help = "Extension given to outputs of INPUTs")]
+    extension: String,
+    #[arg(long = "source-extension", value_name = "EXT", default_value = "φ", help = "Extension of sources picked up in INPUT directories (repeatable)")]
+    source_extensions: Vec<String>,
+    #[arg(short, long, default_value_t = 0, help = "Worker threads for INPUTs (0 uses every CPU)")]
+    jobs: usize,
+    #[arg(long, value_name = "DIR", help = "Reuse outputs of unchanged INPUTs cached in DIR")]
+    cache_dir: Option<PathBuf>,
+    #[arg(long, requires = "inputs", help = "Keep running and re-transpile INPUTs when they change")]
+    watch: bool,
+    #[arg(long, requires = "watch", help = "Watch by polling instead of filesystem notifications")]
+    poll: bool,
+    #[arg(long, value_name = "MS", default_value_t = 1000, help = "Polling interval in milliseconds")]
+    poll_interval: u64,
+    #[arg(long, value_name = "MS", default_value_t = 100, help = "Wait this long after a change for more before re-transpiling")]
+    debounce: u64,
+    #[arg(short, long, help = "JSON mapping of symbols to replacements")]
+    symbols: Option<String>,
+    #[arg(long = "symbols-file", value_name = "PATH", help = "JSON, TOML or YAML mapping file (repeatable, later files override earlier)")]
+    symbols_files: Vec<PathBuf>,
+    #[arg(long, value_name = "NAME|PATH", help = "Start from a built-in dialect or a dialect manifest; --symbols-file and --symbols override it")]
+    dialect: Option<String>,
+    #[arg(long, help = "Refuse sources declaring a dialect version other than the configured one")]
+    strict_dialect: bool,
+    #[arg(long, help = "List the built-in dialects and exit")]
+    list_dialects: bool,
+    #[arg(long, conflicts_with_all = ["inputs", "report", "source_map", "benchmark", "reverse", "serve"], help = "Transpile stdin in pieces instead of reading it whole")]
+    stream: bool,
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 20, help = "Bytes read per piece with --stream")]
+    chunk_size: usize,
+    #[arg(long, help = "Show performance benchmarks")]
+    benchmark: bool,
+    #[arg(long, help = "Bypass threat detection")]
+    bypass: bool,
+    #[arg(long, help = "Serve line-delimited JSON requests on stdin/stdout")]
+    serve: bool,
+    #[arg(long, help = "Convert Python back to PhiCode symbols")]
+    reverse: bool,
+    #[arg(short, long, help = "Write output to a file instead of stdout")]
+    output: Option<PathBuf>,
+    #[arg(long, num_args = 0..=1, value_name = "PATH", help = "Write a Source Map v3 file (defaults to <output>.map)")]
+    source_map: Option<Option<PathBuf>>,
+    #[arg(long, default_value = "<stdin>", help = "Source file name used in diagnostics, reports and source maps")]
+    source_name: String,
+    #[arg(long, value_enum, help = "Collect every security finding and report them in this format")]
+    report: Option<ReportFormat>,
+    #[arg(long, value_name = "PATH", help = "Write the findings report to a file instead of stderr")]
+    report_file: Option<PathBuf>,
+    #[arg(long, value_enum, default_value = "off", help = "Scan the whole transpiled output for threats")]
+    scan_output: ScanMode,
+    #[arg(long, value_name = "PATH", help = "Threat policy file adjusting the built-in rules")]
+    threat_policy: Option<PathBuf>,
+    #[arg(long, value_enum, default_value = "substring", help = "Threat detection backend")]
+    detector: Detector,
+    #[arg(long, value_enum, default_value = "regex", help = "Symbol matching engine")]
+    engine: Engine,
+    #[arg(long, value_enum, default_value = "none", help = "Unicode normalization applied to source code before matching; symbols are never folded")]
+    normalize: Normalize,
+    #[arg(long, value_name = "builtin|PATH", help = "Transpile look-alikes of configured symbols as those symbols, with a warning; builtin has no styled letters such as 𝑓")]
+    confusables: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Normalize {
+    None,
+    Nfc,
+    Nfkc,
+}
+
+impl From<Normalize> for Normalization {
+    fn from(form: Normalize) -> Self {
+        match form {
+            Normalize::None => Normalization::None,
+            Normalize::Nfc => Normalization::Nfc,
+            Normalize::Nfkc => Normalization::Nfkc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Engine {
+    Regex,
+    AhoCorasick,
+}
+
+impl From<Engine> for MatchEngine {
+    fn from(engine: Engine) -> Self {
+        match engine {
+            Engine::Regex => MatchEngine::Regex,
+            Engine::AhoCorasick => MatchEngine::AhoCorasick,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Detector {
+    Substring,
+    Ast,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ScanMode {
+    Off,
+    Warn,
+    Block,
+}
+
+impl From<ScanMode> for OutputScan {
+    fn from(mode: ScanMode) -> Self {
+        match mode {
+            ScanMode::Off => OutputScan::Off,
+            ScanMode::Warn => OutputScan::Warn,
+            ScanMode::Block => OutputScan::Block,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Json,
+    Sarif,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e.downcast_ref::<Diagnostic>() {
+                Some(diagnostic) => eprintln!("{}", diagnostic.0),
+                None => eprintln!("error: {}", e),
             }
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.list_dialects {
+        for dialect in dialect::builtin() {
+            let manifest = dialect.manifest()?;
+            println!("{:<12} {:<8} {} ({} symbols)",
+                manifest.name, manifest.version, manifest.description, manifest.table.mappings.len());
         }
-        self.symbol_bytes = Some(bytes);
+        return Ok(());
+    }
 
-        let mut symbols: Vec<_> = self.mappings.keys().cloned().collect();
-        symbols.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    let manifest = cli.dialect.as_deref().map(dialect::resolve).transpose()?;
 
-        let escaped_symbols: Vec<String> = symbols.iter()
-            .map(|s| {
-                if s.chars().all(|c| c.is_alphanumeric() || c == '_') {
-                    format!(r"\b{}\b", regex::escape(s))
-                } else {
-                    regex::escape(s)
-                }
-            })
-            .collect();
+    if cli.serve {
+        let mut transpiler = SymbolTranspiler::new();
+        transpiler.set_engine(cli.engine.into())?;
+        transpiler.set_normalization(cli.normalize.into())?;
+        transpiler.set_confusables(confusables(cli)?)?;
+        if cli.symbols.is_some() || !cli.symbols_files.is_empty() || manifest.is_some() {
+            transpiler.configure_table(resolve_mappings(cli, manifest.as_ref())?)?;
+        }
+        let mut server = Server::new(transpiler, threat_detector(cli)?, cli.bypass);
+        server.set_dialect(manifest);
+        server.run(io::stdin().lock(), io::stdout().lock())?;
+        return Ok(());
+    }
 
-        let pattern_str = format!("({})", escaped_symbols.join("|"));
-        self.pattern = Some(
-            Regex::new(&pattern_str)
-                .map_err(|e| format!("Regex compilation failed: {}", e))?
-        );
-        Ok(())
-    }
-
-    fn contains_symbols(&self, source: &str) -> bool {
-        match &self.symbol_bytes {
-            Some(bytes) => {
-                let source_bytes = source.as_bytes();
-                for chunk in source_bytes.chunks(64) {
-                    for &byte in chunk {
-                        if byte > 127 && bytes.contains(&byte) {
-                            return true;
-                        }
-                    }
-                }
-                false
-            },
-            None => false,
+    if !cli.inputs.is_empty() {
+        return run_batch(cli, manifest);
+    }
+
+    let mappings = resolve_mappings(cli, manifest.as_ref())?;
+
+    let mut source = String::new();
+
+    if cli.reverse {
+        let mut transpiler = SymbolTranspiler::new();
+        transpiler.set_engine(cli.engine.into())?;
+        transpiler.configure_reverse(mappings.mappings)?;
+        io::stdin().read_to_string(&mut source)?;
+
+        if let Some(table) = transpiler.reverse_table() {
+            for entry in table.ambiguous() {
+                eprintln!("warning: `{}` has several symbols ({}, {}); using `{}`",
+                    entry.python, entry.chosen, entry.alternatives.join(", "), entry.chosen);
+            }
+        }
+        let (result, issues) = transpiler.transpile_reverse(&source)?;
+        for issue in &issues {
+            eprintln!("warning: {}:{}: `{}` does not round-trip: {}", issue.line, issue.column, issue.text, issue.reason);
         }
+        write_output(cli.output.as_ref(), &result)?;
+        return Ok(());
+    }
+
+    let threat_detector = threat_detector(cli)?;
+
+    let confusables = confusables(cli)?;
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.set_engine(cli.engine.into())?;
+    transpiler.set_normalization(cli.normalize.into())?;
+    transpiler.set_confusables(confusables.clone())?;
+    transpiler.configure_table(mappings.clone())?;
+    transpiler.set_output_scan(cli.scan_output.into());
+    for issue in transpiler.validate((!cli.bypass).then_some(&threat_detector)) {
+        eprintln!("warning: {}", issue);
+    }
+
+    if cli.stream {
+        return run_stream(cli, &mut transpiler, &threat_detector, manifest.as_ref());
+    }
+    io::stdin().read_to_string(&mut source)?;
+    // Findings are positioned in the normalized source.
+    if let Cow::Owned(normalized) = transpiler.normalize(&source) {
+        source = normalized;
     }
 
-    pub fn transpile(&mut self, source: &str, threat_detector: &ThreatDetector, bypass_security: bool) -> Result<String, String> {
-        if !self.contains_symbols(source) {
-            return Ok(source.to_string());
+    if let Some(mismatch) = manifest.as_ref().and_then(|m| m.check(&source)) {
+        if cli.strict_dialect {
+            return Err(format!("{}:{}: {}", cli.source_name, mismatch.declared.line, mismatch).into());
         }
+        eprintln!("warning: {}:{}: {}", cli.source_name, mismatch.declared.line, mismatch);
+    }
 
-        let pattern = match &self.pattern {
-            Some(p) => p,
-            None => return Ok(source.to_string()),
+    if let Some(format) = cli.report
+        && !cli.bypass
+    {
+        let findings = match transpiler.transpile_collecting(&source, &threat_detector) {
+            Ok(_) => Vec::new(),
+            Err(Error::Security(findings)) => findings,
+            Err(e) => return Err(e.into()),
         };
+        let rendered = match format {
+            ReportFormat::Json => report::to_json(&findings, &cli.source_name),
+            ReportFormat::Sarif => report::to_sarif(&findings, &cli.source_name),
+        };
+        match &cli.report_file {
+            Some(path) => std::fs::write(path, rendered)?,
+            None => eprintln!("{}", rendered),
+        }
+        if !findings.is_empty() {
+            return Err(diagnose(Error::Security(findings), &source, &cli.source_name));
+        }
+    }
 
-        let mut blocked = false;
-        let result = pattern.replace_all(source, |caps: &Captures| {
-            let matched = &caps[1];
+    let map_path = match &cli.source_map {
+        Some(Some(path)) => Some(path.clone()),
+        Some(None) => match &cli.output {
+            Some(output) => {
+                let mut path = output.clone().into_os_string();
+                path.push(".map");
+                Some(PathBuf::from(path))
+            },
+            None => return Err("--source-map needs a path when writing to stdout".into()),
+        },
+        None => None,
+    };
 
-            if let Some(python_replacement) = self.mappings.get(matched) {
-                if !bypass_security && threat_detector.is_dangerous(python_replacement) {
-                    blocked = true;
-                    return "SECURITY_BLOCKED".to_string();
-                }
-                python_replacement.clone()
-            } else {
-                matched.to_string()
-            }
-        });
+    let result = match &map_path {
+        Some(path) => {
+            let (result, map) = transpiler.transpile_with_map(&source, &threat_detector, cli.bypass)
+                .map_err(|e| diagnose(e, &source, &cli.source_name))?;
+            let file = cli.output.as_ref()
+                .and_then(|p| p.file_name())
+                .map_or_else(|| "<stdout>".to_string(), |n| n.to_string_lossy().into_owned());
+            std::fs::write(path, map.to_v3_json(&source, &result, &file, &cli.source_name))?;
+            result
+        },
+        None => transpiler.transpile(&source, &threat_detector, cli.bypass)
+            .map_err(|e| diagnose(e, &source, &cli.source_name))?,
+    };
 
-        if blocked {
-            return Err("Security: Dangerous pattern detected during transpilation".to_string());
+    print_notes(&cli.source_name, Some(&source), &transpiler.take_warnings(), &transpiler.take_exemptions(), &transpiler.take_confusables());
+
+    if cli.benchmark {
+        // Both engines run on the same mappings and source.
+        for engine in [MatchEngine::Regex, MatchEngine::AhoCorasick] {
+            let start = std::time::Instant::now();
+            let mut transpiler = SymbolTranspiler::new();
+            transpiler.set_engine(engine)?;
+            transpiler.set_normalization(cli.normalize.into())?;
+            transpiler.set_confusables(confusables.clone())?;
+            transpiler.configure_table(mappings.clone())?;
+            let compiled = start.elapsed();
+
+            let start = std::time::Instant::now();
+            let output = transpiler.transpile(&source, &threat_detector, cli.bypass)?;
+            let duration = start.elapsed();
+            let chars_per_sec = if duration.as_secs_f64() > 0.0 {
+                source.len() as f64 / duration.as_secs_f64()
+            } else {
+                f64::INFINITY
+            };
+            eprintln!("{:?}: compiled {} symbols in {:?}, transpiled {} chars in {:?}",
+                engine, mappings.mappings.len(), compiled, source.len(), duration);
+            eprintln!("{:?}: speed {:.0} chars/sec{}",
+                engine, chars_per_sec, if output == result { "" } else { " (output differs)" });
         }
+    }
 
-        Ok(result.to_string())
+    if cli.bypass {
+        eprintln!("⚠️  Security bypass enabled - threats not blocked");
     }
-}
 
-#[derive(Parser)]
-#[command(name = "phicode-transpiler")]
-#[command(about = "Fast symbolic transpiler for PhiCode")]
-struct Cli {
-    #[arg(short, long, help = "JSON mapping of symbols to replacements")]
-    symbols: String,
-    #[arg(long, help = "Show performance benchmarks")]
-    benchmark: bool,
-    #[arg(long, help = "Bypass threat detection")]
-    bypass: bool,
+    write_output(cli.output.as_ref(), &result)?;
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
-    let mappings: AHashMap<String, String> = serde_json::from_str(&cli.symbols)?;
+fn run_stream(cli: &Cli, transpiler: &mut SymbolTranspiler, threat_detector: &ThreatDetector, manifest: Option<&DialectManifest>) -> Result<(), Box<dyn std::error::Error>> {
+    if !cli.bypass && (!matches!(cli.scan_output, ScanMode::Off) || matches!(cli.detector, Detector::Ast)) {
+        return Err("--stream cannot check --scan-output or --detector ast across pieces; transpile the input whole".into());
+    }
+    let mut input = io::BufReader::with_capacity(64 * 1024, io::stdin().lock());
+    // The dialect header sits among the leading comments and blank lines, so
+    // those are read whole, however the input arrives, before streaming.
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        if input.read_until(b'\n', &mut head)? == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&head[start..]);
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            break;
+        }
+    }
+    // Which symbols the rest uses is unknown yet, so every change counts.
+    let text = String::from_utf8_lossy(&head).into_owned();
+    let declared = DialectHeader::find(&text);
+    if let Some(mismatch) = manifest.zip(declared).and_then(|(m, declared)| m.compare(declared)) {
+        if cli.strict_dialect {
+            return Err(format!("{}:{}: {}", cli.source_name, mismatch.declared.line, mismatch).into());
+        }
+        eprintln!("warning: {}:{}: {}", cli.source_name, mismatch.declared.line, mismatch);
+    }
+
+    let output: Box<dyn Write> = match &cli.output {
+        Some(path) => Box::new(io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+    let input = io::Cursor::new(head).chain(input);
+    match transpiler.transpile_stream(input, output, threat_detector, cli.bypass, cli.chunk_size) {
+        Ok(_) => {},
+        Err(Error::Security(violations)) => {
+            let rendered: Vec<String> = violations.iter()
+                .map(|v| format!("error: {}:{}:{}: {}", cli.source_name, v.line, v.column, v.message()))
+                .collect();
+            return Err(Box::new(Diagnostic(rendered.join("\n"))));
+        },
+        Err(e) => return Err(e.into()),
+    }
 
-    let threat_detector = ThreatDetector::new()?;
+    // The streamed source is gone, so findings print without an excerpt.
+    print_notes(&cli.source_name, None, &transpiler.take_warnings(), &transpiler.take_exemptions(), &transpiler.take_confusables());
+    if cli.bypass {
+        eprintln!("⚠️  Security bypass enabled - threats not blocked");
+    }
+    Ok(())
+}
 
+fn run_batch(cli: &Cli, manifest: Option<DialectManifest>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(Some(_)) = &cli.source_map {
+        return Err("--source-map takes no path with INPUTs; maps are written next to each output".into());
+    }
+    let table = resolve_mappings(cli, manifest.as_ref())?;
+    let threat_detector = threat_detector(cli)?;
+    let confusables = confusables(cli)?;
     let mut transpiler = SymbolTranspiler::new();
-    transpiler.configure(mappings)?;
-    let mut source = String::new();
-    io::stdin().read_to_string(&mut source)?;
+    transpiler.set_normalization(cli.normalize.into())?;
+    transpiler.set_confusables(confusables.clone())?;
+    transpiler.configure_table(table.clone())?;
+    for issue in transpiler.validate((!cli.bypass).then_some(&threat_detector)) {
+        eprintln!("warning: {}", issue);
+    }
 
-    let result = transpiler.transpile(&source, &threat_detector, cli.bypass)?;
+    let files = batch::collect_inputs(&cli.inputs, &cli.source_extensions)?;
+    let options = BatchOptions {
+        out_dir: cli.out_dir.clone(),
+        extension: cli.extension.trim_start_matches('.').to_string(),
+        source_extensions: cli.source_extensions.clone(),
+        jobs: cli.jobs,
+        bypass: cli.bypass,
+        output_scan: cli.scan_output.into(),
+        engine: cli.engine.into(),
+        normalization: cli.normalize.into(),
+        confusables,
+        source_maps: cli.source_map.is_some(),
+        dialect: manifest,
+        strict_dialect: cli.strict_dialect,
+        cache_dir: cli.cache_dir.clone(),
+    };
+    let start = std::time::Instant::now();
+    let results = batch::run(&files, &table, &threat_detector, &options)?;
+    let summary = print_results(cli, &results, start.elapsed());
 
-    if cli.benchmark {
-        let start = std::time::Instant::now();
-        let _ = transpiler.transpile(&source, &threat_detector, cli.bypass)?;
-        let duration = start.elapsed();
-        let chars_per_sec = if duration.as_secs_f64() > 0.0 {
-            source.len() as f64 / duration.as_secs_f64()
-        } else {
-            f64::INFINITY
+    if cli.watch {
+        return watch(cli, &table, &threat_detector, &options);
+    }
+    if !summary.is_success() {
+        return Err(format!("{} of {} files were not transpiled", summary.blocked + summary.failed, results.len()).into());
+    }
+    Ok(())
+}
+
+fn watch(cli: &Cli, table: &MappingTable, threat_detector: &ThreatDetector, options: &BatchOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let watcher = SourceWatcher::new(&cli.inputs, Duration::from_millis(cli.poll_interval), cli.poll)?;
+    eprintln!("Watching {} for changes ({})", cli.inputs.join(", "), watcher.backend());
+
+    while let Some(changed) = watcher.next_changes(Duration::from_millis(cli.debounce)) {
+        let changed: Vec<PathBuf> = changed.iter().filter_map(|p| p.canonicalize().ok()).collect();
+        // Inputs are collected again so new files under watched directories are picked up.
+        let files = match batch::collect_inputs(&cli.inputs, &cli.source_extensions) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            },
         };
-        eprintln!("Transpiled {} chars in {:?}", source.len(), duration);
-        eprintln!("Speed: {:.0} chars/sec", chars_per_sec);
+        let files: Vec<_> = files.into_iter()
+            .filter(|f| f.path.canonicalize().is_ok_and(|p| changed.contains(&p)))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        let start = std::time::Instant::now();
+        match batch::run(&files, table, threat_detector, options) {
+            Ok(results) => {
+                print_results(cli, &results, start.elapsed());
+            },
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn print_results(cli: &Cli, results: &[FileResult], duration: Duration) -> Summary {
+    for result in results {
+        let name = result.input.display().to_string();
+        if let Some(mismatch) = &result.dialect
+            && !cli.strict_dialect
+        {
+            eprintln!("warning: {}:{}: {}", name, mismatch.declared.line, mismatch);
+        }
+        print_notes(&name, Some(&result.source), &result.warnings, &result.exemptions, &result.confusables);
+        match &result.status {
+            FileStatus::Blocked(violations) => eprintln!("{}", diagnose(Error::Security(violations.clone()), &result.source, &name)),
+            FileStatus::Failed(message) => eprintln!("error: {}: {}", name, message),
+            FileStatus::Transpiled if cli.watch => eprintln!("{} -> {}", name, result.output.display()),
+            FileStatus::Transpiled | FileStatus::Unchanged => {},
+        }
     }
 
+    let summary = Summary::new(results);
+    eprintln!("{} transpiled, {} unchanged, {} blocked, {} failed ({} files in {:?})",
+        summary.transpiled, summary.unchanged, summary.blocked, summary.failed, results.len(), duration);
+    if summary.cache.hits + summary.cache.misses > 0 {
+        eprintln!("cache: {} hits, {} misses ({:.1}% hit rate)",
+            summary.cache.hits, summary.cache.misses, summary.cache.hit_rate() * 100.0);
+    }
     if cli.bypass {
         eprintln!("⚠️  Security bypass enabled - threats not blocked");
     }
+    summary
+}
 
-    io::stdout().write_all(result.as_bytes())?;
-    Ok(())
+// Prints what a transpilation allowed but reports: output warnings, rendered
+// against `source` when it is at hand, trusted exemptions and confusables.
+fn print_notes(name: &str, source: Option<&str>, warnings: &[SecurityViolation], exemptions: &[SecurityViolation], confusables: &[Confusable]) {
+    for warning in warnings {
+        match source {
+            Some(source) => eprintln!("{}", warning.render(source, name, "warning")),
+            None => eprintln!("warning: {}:{}:{}: {}", name, warning.line, warning.column, warning.message()),
+        }
+    }
+    for exemption in exemptions {
+        eprintln!("note: {}:{}:{}: trusted symbol `{}` allowed: {}",
+            name, exemption.line, exemption.column, exemption.symbol, exemption.message());
+    }
+    for confusable in confusables {
+        eprintln!("warning: {}:{}:{}: {}", name, confusable.line, confusable.column, confusable);
+    }
+}
+
+fn threat_detector(cli: &Cli) -> Result<ThreatDetector, Error> {
+    let policy = match &cli.threat_policy {
+        Some(path) => ThreatPolicy::load(path)?,
+        None => ThreatPolicy::builtin(),
+    };
+    let backend = match cli.detector {
+        Detector::Substring => DetectorBackend::Substring,
+        Detector::Ast => DetectorBackend::Ast,
+    };
+    ThreatDetector::with_backend(&policy, backend)
+}
+
+fn confusables(cli: &Cli) -> Result<Confusables, Error> {
+    cli.confusables.as_deref().map_or_else(|| Ok(Confusables::default()), Confusables::resolve)
+}
+
+fn resolve_mappings(cli: &Cli, manifest: Option<&DialectManifest>) -> Result<MappingTable, Box<dyn std::error::Error>> {
+    if cli.symbols.is_none() && cli.symbols_files.is_empty() && manifest.is_none() {
+        return Err("No mappings given: use --dialect, --symbols or --symbols-file".into());
+    }
+
+    let mut layers: Vec<MappingLayer> = cli.symbols_files.iter().cloned().map(MappingLayer::File).collect();
+    if let Some(symbols) = &cli.symbols {
+        let table = mapping_file::parse_mappings(symbols, "json", "--symbols")?;
+        layers.push(MappingLayer::Table { table, origin: "--symbols".to_string() });
+    }
+    let base = manifest.map(|m| m.table.clone()).unwrap_or_default();
+    let (mappings, overridden) = mapping_file::layer_mappings(base, layers)?;
+
+    for key in &overridden {
+        eprintln!("note: `{}` overridden by {}: `{}` -> `{}`", key.symbol, key.origin, key.previous, key.replacement);
+    }
+    Ok(mappings)
+}
+
+#[derive(Debug)]
+struct Diagnostic(String);
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+fn diagnose(error: Error, source: &str, name: &str) -> Box<dyn std::error::Error> {
+    match error {
+        Error::Security(violations) => {
+            let rendered: Vec<String> = violations.iter()
+                .map(|v| v.render(source, name, "error"))
+                .collect();
+            Box::new(Diagnostic(rendered.join("\n").trim_end().to_string()))
+        },
+        other => Box::new(other),
+    }
+}
+
+fn write_output(path: Option<&PathBuf>, result: &str) -> io::Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, result),
+        None => io::stdout().write_all(result.as_bytes()),
+    }
 }
\ No newline at end of file
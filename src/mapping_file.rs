@@ -0,0 +1,202 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use ahash::{AHashMap, AHashSet};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Symbol replacements plus the symbols trusted to skip threat checks and
+/// those used as infix operators.
+///
+/// In a mapping document each value is either the replacement or a table.
+/// `trusted` marks symbols meant to expand to something the threat detector
+/// would refuse. Symbols that are Python identifiers only match as whole
+/// tokens; `infix` lets one match between identifiers too, spacing its
+/// replacement from them:
+///
+/// ```toml
+/// "λ" = "lambda"
+/// "∂" = { replacement = "open(", trusted = true }
+/// "ε" = { replacement = "in", infix = true }   # `xεs` becomes `x in s`
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MappingTable {
+    pub mappings: AHashMap<String, String>,
+    pub trusted: AHashSet<String>,
+    pub infix: AHashSet<String>,
+}
+
+impl MappingTable {
+    /// Interprets a parsed mapping document; `origin` names it in errors.
+    pub fn from_document(document: &Value, origin: &str) -> Result<Self> {
+        let invalid = |message: String| Error::Document { origin: origin.to_string(), message };
+        let entries = document.as_object()
+            .ok_or_else(|| invalid("Mappings must be a table of symbols".to_string()))?;
+        let mut table = Self::default();
+        for (symbol, entry) in entries {
+            let replacement = match entry {
+                Value::String(replacement) => replacement,
+                Value::Object(fields) => {
+                    let flag = |name: &str| fields.get(name).and_then(Value::as_bool).unwrap_or(false);
+                    if flag("trusted") {
+                        table.trusted.insert(symbol.clone());
+                    }
+                    if flag("infix") {
+                        table.infix.insert(symbol.clone());
+                    }
+                    fields.get("replacement").and_then(Value::as_str)
+                        .ok_or_else(|| invalid(format!("`{}` needs a string `replacement`", symbol)))?
+                },
+                _ => return Err(invalid(format!("`{}` must map to a string or a table", symbol))),
+            };
+            table.mappings.insert(symbol.clone(), replacement.to_string());
+        }
+        Ok(table)
+    }
+}
+
+/// A symbol whose replacement was changed by a later mapping layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverriddenKey {
+    pub symbol: String,
+    pub previous: String,
+    pub replacement: String,
+    pub origin: String,
+}
+
+/// Parses a document in `format` (`toml`, `yaml`/`yml`, anything else is JSON).
+pub fn parse_document<T: DeserializeOwned>(text: &str, format: &str, origin: &str) -> Result<T> {
+    let parsed = match format {
+        "toml" => toml::from_str(text).map_err(|e| format!("Invalid TOML: {}", e)),
+        "yaml" | "yml" => serde_yaml::from_str(text).map_err(|e| format!("Invalid YAML: {}", e)),
+        _ => serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e)),
+    };
+    parsed.map_err(|message| Error::Document { origin: origin.to_string(), message })
+}
+
+/// Reads a document, picking the format from the file extension.
+pub fn read_document<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let origin = path.display().to_string();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::Document { origin: origin.clone(), message: format!("Cannot read file: {}", e) })?;
+    let format = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    parse_document(&text, &format, &origin)
+}
+
+/// Parses a mapping table in `format`.
+pub fn parse_mappings(text: &str, format: &str, origin: &str) -> Result<MappingTable> {
+    MappingTable::from_document(&parse_document(text, format, origin)?, origin)
+}
+
+/// Reads a mapping table from a JSON, TOML or YAML file.
+pub fn load_mappings(path: &Path) -> Result<MappingTable> {
+    MappingTable::from_document(&read_document(path)?, &path.display().to_string())
+}
+
+/// One layer of a mapping stack, merged over the layers before it.
+#[derive(Debug, Clone)]
+pub enum MappingLayer {
+    /// A JSON, TOML or YAML mapping file.
+    File(PathBuf),
+    /// A table given inline; `origin` names it in override reports.
+    Table { table: MappingTable, origin: String },
+}
+
+/// Merges `layers` in order over `base`, such as a dialect's table, later
+/// layers overriding earlier ones. Returns the merged table and every key a
+/// layer overrode.
+pub fn layer_mappings(base: MappingTable, layers: Vec<MappingLayer>) -> Result<(MappingTable, Vec<OverriddenKey>)> {
+    let mut merged = base;
+    let mut overridden = Vec::new();
+    for layer in layers {
+        let (table, origin) = match layer {
+            MappingLayer::File(path) => (load_mappings(&path)?, path.display().to_string()),
+            MappingLayer::Table { table, origin } => (table, origin),
+        };
+        overridden.extend(merge_mappings(&mut merged, table, &origin));
+    }
+    Ok((merged, overridden))
+}
+
+/// Merges `layer` over `merged`, reporting every key whose replacement changed.
+/// Trust and infix belong to an entry, so a redefined symbol keeps only the layer's.
+pub fn merge_mappings(merged: &mut MappingTable, layer: MappingTable, origin: &str) -> Vec<OverriddenKey> {
+    let mut overridden = Vec::new();
+    for (symbol, replacement) in layer.mappings {
+        for (flags, layer_flags) in [(&mut merged.trusted, &layer.trusted), (&mut merged.infix, &layer.infix)] {
+            if layer_flags.contains(&symbol) {
+                flags.insert(symbol.clone());
+            } else {
+                flags.remove(&symbol);
+            }
+        }
+        if let Some(previous) = merged.mappings.insert(symbol.clone(), replacement.clone())
+            && previous != replacement
+        {
+            overridden.push(OverriddenKey { symbol, previous, replacement, origin: origin.to_string() });
+        }
+    }
+    overridden.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    overridden
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> MappingTable {
+        parse_mappings(text, "toml", "test").unwrap()
+    }
+
+    fn inline(text: &str, origin: &str) -> MappingLayer {
+        MappingLayer::Table { table: table(text), origin: origin.to_string() }
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let path = std::env::temp_dir().join(format!("phirust-layers-{}.yaml", std::process::id()));
+        std::fs::write(&path, "\"λ\": lambda\n\"∂\": { replacement: \"open(\", trusted: true }\n\"π\": print\n").unwrap();
+        let dialect = table("\"ƒ\" = \"def\"\n\"π\" = \"print\"\n\"∂\" = \"del\"\n\"∈\" = { replacement = \"in\", infix = true }\n");
+
+        let layers = vec![
+            MappingLayer::File(path.clone()),
+            inline("\"π\" = \"log\"\n\"∈\" = \"in\"\n", "--symbols"),
+        ];
+        let (merged, overridden) = layer_mappings(dialect, layers).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let replacement = |symbol: &str| merged.mappings.get(symbol).map(String::as_str);
+        assert_eq!(merged.mappings.len(), 5);
+        assert_eq!(replacement("ƒ"), Some("def"));
+        assert_eq!(replacement("λ"), Some("lambda"));
+        assert_eq!(replacement("∂"), Some("open("));
+        assert_eq!(replacement("π"), Some("log"));
+        assert!(merged.trusted.contains("∂"));
+        // Redefining a symbol drops the flags of the entry it replaces.
+        assert!(merged.infix.is_empty());
+
+        let notes: Vec<(&str, &str, &str, &str)> = overridden.iter()
+            .map(|k| (k.symbol.as_str(), k.previous.as_str(), k.replacement.as_str(), k.origin.as_str()))
+            .collect();
+        assert_eq!(notes, [("∂", "del", "open(", path.display().to_string().as_str()), ("π", "print", "log", "--symbols")]);
+    }
+
+    #[test]
+    fn unchanged_replacements_are_not_overrides() {
+        let (merged, overridden) = layer_mappings(table("\"ƒ\" = \"def\"\n"), vec![inline("\"ƒ\" = \"def\"\n", "--symbols")]).unwrap();
+        assert_eq!(merged.mappings.len(), 1);
+        assert!(overridden.is_empty());
+    }
+
+    #[test]
+    fn unreadable_layers_fail() {
+        let missing = MappingLayer::File(PathBuf::from("/nonexistent/mappings.json"));
+        let error = layer_mappings(MappingTable::default(), vec![missing]).unwrap_err();
+        assert!(matches!(error, Error::Document { ref origin, .. } if origin == "/nonexistent/mappings.json"));
+    }
+}
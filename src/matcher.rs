@@ -0,0 +1,232 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+use regex::Regex;
+use std::ops::Range;
+
+/// How symbols are found in source text. Both engines find the same matches:
+/// the leftmost, and among those the longest, that respects the needles'
+/// identifier boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchEngine {
+    /// One alternation regex, longest symbols first.
+    #[default]
+    Regex,
+    /// A leftmost-longest Aho-Corasick automaton; compiles and matches faster
+    /// for large symbol tables.
+    AhoCorasick,
+}
+
+/// A text to find, and whether its start and end must not touch identifier
+/// characters (Unicode XID_Continue, as in Python identifiers).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Needle {
+    pub text: String,
+    pub word_start: bool,
+    pub word_end: bool,
+}
+
+impl Needle {
+    /// A symbol that only matches as a whole token if it is a Python
+    /// identifier, and anywhere otherwise.
+    pub fn symbol(text: &str) -> Self {
+        let word = is_identifier(text);
+        Self { text: text.to_string(), word_start: word, word_end: word }
+    }
+
+    /// A text matching anywhere, such as an infix operator symbol.
+    pub fn anywhere(text: &str) -> Self {
+        Self { text: text.to_string(), word_start: false, word_end: false }
+    }
+
+    /// A text whose ends each keep a boundary if they are identifier characters.
+    pub fn bounded(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            word_start: text.chars().next().is_some_and(is_identifier_char),
+            word_end: text.chars().last().is_some_and(is_identifier_char),
+        }
+    }
+}
+
+/// Finds a set of [`Needle`]s in text with one [`MatchEngine`].
+#[derive(Debug, Clone)]
+pub struct SymbolMatcher {
+    inner: Inner,
+    /// Sorted by text, to look up the needle of a regex match.
+    needles: Vec<Needle>,
+    prefilter: Prefilter,
+}
+
+#[derive(Debug, Clone)]
+enum Inner {
+    /// The alternation, and the same anchored at the start of the haystack.
+    Regex { pattern: Regex, anchored: Regex },
+    AhoCorasick(AhoCorasick),
+}
+
+impl SymbolMatcher {
+    /// Compiles `needles`, none of which may be empty.
+    pub fn new(mut needles: Vec<Needle>, engine: MatchEngine) -> Result<Self> {
+        needles.sort_by(|a, b| a.text.cmp(&b.text));
+        needles.dedup_by(|a, b| a.text == b.text);
+        let prefilter = Prefilter::new(&needles);
+        let inner = match engine {
+            MatchEngine::Regex => {
+                // Alternation picks the first alternative that matches, so the
+                // longest go first.
+                let mut texts: Vec<&str> = needles.iter().map(|n| n.text.as_str()).collect();
+                texts.sort_by_key(|t| std::cmp::Reverse(t.len()));
+                let alternation = texts.iter().map(|t| regex::escape(t)).collect::<Vec<_>>().join("|");
+                let compile = |pattern: String| Regex::new(&pattern).map_err(|e| Error::Pattern(e.to_string()));
+                Inner::Regex {
+                    pattern: compile(format!("(?:{})", alternation))?,
+                    anchored: compile(format!("^(?:{})", alternation))?,
+                }
+            },
+            MatchEngine::AhoCorasick => {
+                let automaton = AhoCorasick::builder()
+                    .match_kind(MatchKind::LeftmostLongest)
+                    .start_kind(StartKind::Both)
+                    .build(needles.iter().map(|n| &n.text))
+                    .map_err(|e| Error::Pattern(e.to_string()))?;
+                Inner::AhoCorasick(automaton)
+            },
+        };
+        Ok(Self { inner, needles, prefilter })
+    }
+
+    pub fn engine(&self) -> MatchEngine {
+        match self.inner {
+            Inner::Regex { .. } => MatchEngine::Regex,
+            Inner::AhoCorasick(_) => MatchEngine::AhoCorasick,
+        }
+    }
+
+    /// Byte ranges of the non-overlapping matches in `text`, in order.
+    pub fn find_iter<'m, 't>(&'m self, text: &'t str) -> Matches<'m, 't> {
+        Matches { matcher: self, text, pos: 0 }
+    }
+
+    /// A cheap check run before matching: false only if `text` holds no
+    /// byte a needle starts with, and so cannot contain a match.
+    pub fn may_match(&self, text: &str) -> bool {
+        self.prefilter.may_match(text.as_bytes())
+    }
+
+    // The leftmost-longest needle occurrence starting at or after `pos`,
+    // ignoring boundaries.
+    fn leftmost(&self, text: &str, pos: usize) -> Option<Range<usize>> {
+        match &self.inner {
+            Inner::Regex { pattern, .. } => pattern.find_at(text, pos).map(|m| m.range()),
+            Inner::AhoCorasick(automaton) => automaton.find(Input::new(text).span(pos..text.len())).map(|m| m.range()),
+        }
+    }
+
+    // The end of the longest needle occurrence at `start` that ends by `limit`.
+    fn longest_at(&self, text: &str, start: usize, limit: usize) -> Option<usize> {
+        match &self.inner {
+            Inner::Regex { anchored, .. } => anchored.find(&text[start..limit]).map(|m| start + m.end()),
+            Inner::AhoCorasick(automaton) => automaton
+                .find(Input::new(text).span(start..limit).anchored(Anchored::Yes))
+                .map(|m| m.end()),
+        }
+    }
+
+    fn fits(&self, text: &str, range: Range<usize>) -> bool {
+        let Ok(index) = self.needles.binary_search_by(|n| n.text.as_str().cmp(&text[range.clone()])) else {
+            return false;
+        };
+        let needle = &self.needles[index];
+        (!needle.word_start || !text[..range.start].chars().next_back().is_some_and(is_identifier_char))
+            && (!needle.word_end || !text[range.end..].chars().next().is_some_and(is_identifier_char))
+    }
+}
+
+/// Iterator returned by [`SymbolMatcher::find_iter`].
+pub struct Matches<'m, 't> {
+    matcher: &'m SymbolMatcher,
+    text: &'t str,
+    pos: usize,
+}
+
+impl Iterator for Matches<'_, '_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        let text = self.text;
+        while self.pos < text.len() {
+            let found = self.matcher.leftmost(text, self.pos)?;
+            let start = found.start;
+            let mut end = Some(found.end);
+            // When the longest match breaks a boundary, a shorter one starting
+            // at the same place may not.
+            while let Some(candidate) = end {
+                if self.matcher.fits(text, start..candidate) {
+                    self.pos = candidate;
+                    return Some(start..candidate);
+                }
+                let limit = candidate - text[..candidate].chars().next_back().map_or(0, char::len_utf8);
+                end = if limit > start { self.matcher.longest_at(text, start, limit) } else { None };
+            }
+            self.pos = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+}
+
+// The distinct first bytes of the needles. Up to three are searched with
+// memchr; more are looked up in a table.
+#[derive(Debug, Clone)]
+struct Prefilter {
+    starts: Vec<u8>,
+    table: [bool; 256],
+}
+
+impl Prefilter {
+    fn new(needles: &[Needle]) -> Self {
+        let mut table = [false; 256];
+        for needle in needles {
+            if let Some(&first) = needle.text.as_bytes().first() {
+                table[first as usize] = true;
+            }
+        }
+        let starts = (0..=u8::MAX).filter(|&b| table[b as usize]).collect();
+        Self { starts, table }
+    }
+
+    fn may_match(&self, bytes: &[u8]) -> bool {
+        match *self.starts.as_slice() {
+            [] => false,
+            [a] => memchr::memchr(a, bytes).is_some(),
+            [a, b] => memchr::memchr2(a, b, bytes).is_some(),
+            [a, b, c] => memchr::memchr3(a, b, c, bytes).is_some(),
+            _ => bytes.iter().any(|&b| self.table[b as usize]),
+        }
+    }
+}
+
+/// Whether `text` is a Python identifier: an XID_Start character or `_`,
+/// then XID_Continue characters.
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c == '_' || unicode_ident::is_xid_start(c))
+        && chars.all(unicode_ident::is_xid_continue)
+}
+
+/// Whether `text` is a Python keyword, soft keywords included.
+pub fn is_keyword(text: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "case", "class", "continue", "def",
+        "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+        "match", "nonlocal", "not", "or", "pass", "raise", "return", "try", "type", "while", "with", "yield",
+    ];
+    KEYWORDS.contains(&text)
+}
+
+/// Whether `c` can continue a Python identifier, so a token cannot end before it.
+pub fn is_identifier_char(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
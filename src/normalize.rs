@@ -0,0 +1,192 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use crate::lexer::{self, SegmentKind};
+use crate::mapping_file;
+use ahash::AHashMap;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// The Unicode normalization form applied to source code before matching, so
+/// differently encoded copies of a symbol still match. Symbols themselves are
+/// only composed canonically: NFKC would turn `ℂ` into `C` and `‼` into `!!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    #[default]
+    None,
+    /// Canonical composition: `e` followed by a combining accent equals `é`.
+    Nfc,
+    /// Compatibility composition, as Python applies to identifiers: also folds
+    /// ligatures, full-width and styled letters.
+    Nfkc,
+}
+
+impl Normalization {
+    /// `text` in this form, borrowed when it already is.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let quick = match self {
+            Normalization::None => return Cow::Borrowed(text),
+            Normalization::Nfc => is_nfc_quick(text.chars()),
+            Normalization::Nfkc => is_nfkc_quick(text.chars()),
+        };
+        if quick == IsNormalized::Yes {
+            return Cow::Borrowed(text);
+        }
+        let normalized: String = match self {
+            Normalization::Nfkc => text.nfkc().collect(),
+            _ => text.nfc().collect(),
+        };
+        if normalized == text { Cow::Borrowed(text) } else { Cow::Owned(normalized) }
+    }
+
+    /// `symbol` as it is matched: canonically composed under either form, so
+    /// a decomposed copy in the source still matches, but never folded.
+    pub fn apply_to_symbol<'a>(&self, symbol: &'a str) -> Cow<'a, str> {
+        match self {
+            Normalization::None => Cow::Borrowed(symbol),
+            _ => Normalization::Nfc.apply(symbol),
+        }
+    }
+
+    /// Normalizes the code of `source`, leaving strings and comments alone.
+    /// A line of code whose normalization would add or remove characters the
+    /// lexer relies on, such as a full-width quote becoming `"`, is kept as is
+    /// so normalizing never changes where literals start and end. Occurrences
+    /// of `kept`, the symbols this form would fold, are left as they are.
+    pub fn apply_to_code<'a>(&self, source: &'a str, kept: &[String]) -> Cow<'a, str> {
+        if let Cow::Borrowed(_) = self.apply(source) {
+            return Cow::Borrowed(source);
+        }
+        let mut output = String::with_capacity(source.len());
+        for segment in lexer::segments(source) {
+            let text = &source[segment.range];
+            if segment.kind == SegmentKind::Literal {
+                output.push_str(text);
+                continue;
+            }
+            // Normalization never combines characters across a line break.
+            for line in text.split_inclusive('\n') {
+                let normalized = self.apply_around(line, kept);
+                if structure(&normalized).eq(structure(line)) {
+                    output.push_str(&normalized);
+                } else {
+                    output.push_str(line);
+                }
+            }
+        }
+        Cow::Owned(output)
+    }
+
+    // Normalizes `text` piecewise between occurrences of `kept`.
+    fn apply_around<'a>(&self, text: &'a str, kept: &[String]) -> Cow<'a, str> {
+        let mut output = String::with_capacity(text.len());
+        let mut start = 0;
+        let mut index = 0;
+        while index < text.len() {
+            match kept.iter().find(|symbol| text[index..].starts_with(symbol.as_str())) {
+                Some(symbol) => {
+                    output.push_str(&self.apply(&text[start..index]));
+                    output.push_str(symbol);
+                    index += symbol.len();
+                    start = index;
+                },
+                None => index += text[index..].chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        if start == 0 {
+            return self.apply(text);
+        }
+        output.push_str(&self.apply(&text[start..]));
+        Cow::Owned(output)
+    }
+}
+
+fn structure(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().filter(|c| matches!(c, '\'' | '"' | '#' | '\\' | '\n' | '\r' | '(' | ')' | '[' | ']' | '{' | '}' | ':' | '!'))
+}
+
+// No identifier letters: `ϵ` or a styled `𝑓` may well be a variable name.
+const BUILTIN_CONFUSABLES: &[(&str, &str)] = &[
+    ("⟶", "→"), ("➔", "→"), ("➜", "→"), ("➝", "→"), ("⇾", "→"), ("⭢", "→"), ("🡒", "→"),
+    ("⟵", "←"), ("⇽", "←"), ("⭠", "←"), ("🡐", "←"),
+    ("⟹", "⇒"), ("⟾", "⇒"),
+    ("∊", "∈"),
+    ("⋀", "∧"), ("⋁", "∨"),
+    ("≣", "≡"),
+    ("✔", "✓"), ("🗸", "✓"),
+    ("∅", "Ø"), ("⌀", "Ø"),
+    ("𝜕", "∂"),
+    ("⋅⋅⋅", "⋯"), ("…", "⋯"),
+];
+
+/// Look-alike texts and the symbols they imitate, such as `➔` for `→`.
+///
+/// A confusable is only matched when the symbol it imitates is configured,
+/// and each use is reported. A confusable that is an identifier only matches
+/// as a whole identifier, even if the symbol it imitates is infix. A
+/// confusable that the normalization form itself rewrites (NFKC turns `𝜕`
+/// into `∂`) is left to the normalization.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Confusables {
+    pub map: AHashMap<String, String>,
+}
+
+impl Confusables {
+    /// Common homoglyphs of the PhiCode symbols and arrows. Letters such as a
+    /// styled `𝑓` for `ƒ` are left out, since they may be variable names; a
+    /// table listing them matches them as whole identifiers only.
+    pub fn builtin() -> Self {
+        let map = BUILTIN_CONFUSABLES.iter().map(|(found, symbol)| (found.to_string(), symbol.to_string())).collect();
+        Self { map }
+    }
+
+    /// Interprets a table of look-alike texts to the symbols they stand for.
+    pub fn from_document(document: &Value, origin: &str) -> Result<Self> {
+        let invalid = |message: String| Error::Document { origin: origin.to_string(), message };
+        let entries = document.as_object()
+            .ok_or_else(|| invalid("Confusables must be a table of look-alikes to symbols".to_string()))?;
+        let mut map = AHashMap::new();
+        for (found, symbol) in entries {
+            let symbol = symbol.as_str()
+                .ok_or_else(|| invalid(format!("`{}` must map to a symbol", found)))?;
+            if found.is_empty() {
+                return Err(invalid("Empty confusable".to_string()));
+            }
+            map.insert(found.clone(), symbol.to_string());
+        }
+        Ok(Self { map })
+    }
+
+    /// Reads a JSON, TOML or YAML confusables table.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_document(&mapping_file::read_document(path)?, &path.display().to_string())
+    }
+
+    /// `builtin` for the built-in table, anything else is read as a file.
+    pub fn resolve(name: &str) -> Result<Self> {
+        match name {
+            "builtin" => Ok(Self::builtin()),
+            path => Self::load(Path::new(path)),
+        }
+    }
+}
+
+/// A look-alike found in the source and transpiled as the symbol it imitates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confusable {
+    pub found: String,
+    pub symbol: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Confusable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` looks like `{}` and was transpiled as it", self.found, self.symbol)
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result};
+use crate::mapping_file;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+/// How serious a threat rule is; reports use it to pick a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "low" => Some(Severity::Low),
+            "medium" => Some(Severity::Medium),
+            "high" => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A substring the threat detector looks for, with its category and severity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreatRule {
+    pub pattern: String,
+    pub category: String,
+    pub severity: Severity,
+}
+
+/// The set of rules a [`ThreatDetector`](crate::ThreatDetector) is built from.
+///
+/// A policy file (JSON, TOML or YAML) is applied on top of the built-in rules:
+///
+/// ```toml
+/// replace = false                       # true starts from an empty rule set
+/// remove = ["input(", "raw_input("]     # drop individual patterns
+/// remove_categories = ["introspection"] # drop whole categories
+///
+/// [categories]
+/// filesystem = "critical"               # re-rate every rule in a category
+///
+/// [[rules]]                             # add, or replace a pattern's rule
+/// pattern = "socket."
+/// category = "network"
+/// severity = "high"                     # defaults to the category's severity
+///
+/// [imports]                             # see `ImportPolicy`
+/// deny = ["subprocess", "ctypes"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreatPolicy {
+    rules: Vec<ThreatRule>,
+    imports: Option<ImportPolicy>,
+}
+
+/// What happens to generated code importing a module the policy refuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportAction {
+    Warn,
+    Block,
+}
+
+/// Controls which modules `import` and `from ... import` statements in the
+/// transpiled output may name.
+///
+/// Patterns are dotted module paths where `*` stands for any one component,
+/// and match the module and everything below it: `os` covers `os.path`, and
+/// `xml.*` covers `xml.etree.ElementTree` but not `xml` itself. A module is
+/// refused if a `deny` pattern matches it, or if there is an `allow` list and
+/// none of its patterns do. `from a import b` is checked as `a.b`; relative
+/// imports are not checked.
+///
+/// ```toml
+/// [imports]
+/// allow = ["json", "math", "collections.*"]
+/// deny = ["os.system"]
+/// action = "warn"                       # defaults to "block"
+/// severity = "critical"                 # defaults to "high"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+    action: ImportAction,
+    severity: Severity,
+}
+
+impl ImportPolicy {
+    /// Refuses every module not matching one of `patterns`.
+    pub fn allow(patterns: Vec<String>) -> Self {
+        Self { allow: Some(patterns), deny: Vec::new(), action: ImportAction::Block, severity: Severity::High }
+    }
+
+    /// Refuses every module matching one of `patterns`.
+    pub fn deny(patterns: Vec<String>) -> Self {
+        Self { allow: None, deny: patterns, action: ImportAction::Block, severity: Severity::High }
+    }
+
+    pub fn with_action(mut self, action: ImportAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn action(&self) -> ImportAction {
+        self.action
+    }
+
+    /// The rule refusing `module`, if any. Its pattern is the matching `deny`
+    /// pattern, or empty when the module is missing from the allow list.
+    pub fn check(&self, module: &str) -> Option<ThreatRule> {
+        let rule = |pattern: &str| ThreatRule {
+            pattern: pattern.to_string(),
+            category: "import".to_string(),
+            severity: self.severity,
+        };
+        if let Some(pattern) = self.deny.iter().find(|p| module_matches(p, module)) {
+            return Some(rule(pattern));
+        }
+        match &self.allow {
+            Some(allow) if !allow.iter().any(|p| module_matches(p, module)) => Some(rule("")),
+            _ => None,
+        }
+    }
+
+    fn parse(value: &Value) -> std::result::Result<Self, String> {
+        let table = value.as_object()
+            .ok_or_else(|| "`imports` must be a table".to_string())?;
+        let allow = match table.get("allow") {
+            Some(allow) => Some(string_list(Some(allow), "imports.allow")?),
+            None => None,
+        };
+        let deny = string_list(table.get("deny"), "imports.deny")?;
+        let action = match table.get("action").map(|a| a.as_str()) {
+            None | Some(Some("block")) => ImportAction::Block,
+            Some(Some("warn")) => ImportAction::Warn,
+            Some(_) => return Err("`imports.action` must be \"warn\" or \"block\"".to_string()),
+        };
+        let severity = match table.get("severity") {
+            Some(value) => value.as_str()
+                .and_then(Severity::parse)
+                .ok_or_else(|| "Unknown severity for `imports`".to_string())?,
+            None => Severity::High,
+        };
+        Ok(Self { allow, deny, action, severity })
+    }
+}
+
+// True if `pattern` matches `module` or one of its parent packages.
+fn module_matches(pattern: &str, module: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let module: Vec<&str> = module.split('.').collect();
+    module.len() >= pattern.len()
+        && pattern.iter().zip(&module).all(|(p, m)| *p == "*" || p == m)
+}
+
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("code-execution", &[
+        "eval(", "eval (", "exec(", "exec (", "compile(", "compile (",
+        "os.system(", "os.system (", "subprocess.", "__import__",
+    ]),
+    ("introspection", &[
+        "getattr(__builtins__", "getattr(__builtins__,", "globals(", "globals (",
+        "locals(", "locals (", "vars(", "vars (", "dir(", "dir (",
+    ]),
+    ("filesystem", &["open(", "open ("]),
+    ("interactive", &["input(", "raw_input("]),
+];
+
+/// Severity given to rules of `category` when none is stated.
+pub fn default_severity(category: &str) -> Severity {
+    match category {
+        "code-execution" => Severity::Critical,
+        "filesystem" | "network" => Severity::High,
+        "introspection" => Severity::Medium,
+        "interactive" => Severity::Low,
+        _ => Severity::Medium,
+    }
+}
+
+impl Default for ThreatPolicy {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ThreatPolicy {
+    /// The built-in rules, grouped into categories.
+    pub fn builtin() -> Self {
+        let rules = BUILTIN.iter()
+            .flat_map(|(category, patterns)| patterns.iter().map(move |pattern| ThreatRule {
+                pattern: pattern.to_string(),
+                category: category.to_string(),
+                severity: default_severity(category),
+            }))
+            .collect();
+        Self { rules, imports: None }
+    }
+
+    pub fn from_rules(rules: Vec<ThreatRule>) -> Self {
+        Self { rules, imports: None }
+    }
+
+    pub fn with_imports(mut self, imports: ImportPolicy) -> Self {
+        self.imports = Some(imports);
+        self
+    }
+
+    pub fn rules(&self) -> &[ThreatRule] {
+        &self.rules
+    }
+
+    pub fn imports(&self) -> Option<&ImportPolicy> {
+        self.imports.as_ref()
+    }
+
+    /// Reads a policy file and applies it on top of the built-in rules.
+    pub fn load(path: &Path) -> Result<Self> {
+        let document: Value = mapping_file::read_document(path)?;
+        let mut policy = Self::builtin();
+        policy.apply(&document, &path.display().to_string())?;
+        Ok(policy)
+    }
+
+    /// Applies a parsed policy document; `origin` names it in errors.
+    pub fn apply(&mut self, document: &Value, origin: &str) -> Result<()> {
+        let invalid = |message: String| Error::Document { origin: origin.to_string(), message };
+        let table = document.as_object()
+            .ok_or_else(|| invalid("Policy must be a table".to_string()))?;
+
+        if table.get("replace").and_then(Value::as_bool).unwrap_or(false) {
+            self.rules.clear();
+            self.imports = None;
+        }
+        for pattern in string_list(table.get("remove"), "remove").map_err(invalid)? {
+            self.rules.retain(|r| r.pattern != pattern);
+        }
+        for category in string_list(table.get("remove_categories"), "remove_categories").map_err(invalid)? {
+            self.rules.retain(|r| r.category != category);
+        }
+
+        if let Some(categories) = table.get("categories") {
+            let categories = categories.as_object()
+                .ok_or_else(|| invalid("`categories` must map names to severities".to_string()))?;
+            for (category, severity) in categories {
+                let severity = severity.as_str()
+                    .and_then(Severity::parse)
+                    .ok_or_else(|| invalid(format!("Unknown severity for category `{}`", category)))?;
+                for rule in self.rules.iter_mut().filter(|r| &r.category == category) {
+                    rule.severity = severity;
+                }
+            }
+        }
+
+        if let Some(rules) = table.get("rules") {
+            let rules = rules.as_array()
+                .ok_or_else(|| invalid("`rules` must be a list".to_string()))?;
+            for rule in rules {
+                let pattern = rule.get("pattern").and_then(Value::as_str)
+                    .filter(|p| !p.is_empty())
+                    .ok_or_else(|| invalid("Every rule needs a non-empty `pattern`".to_string()))?;
+                let category = rule.get("category").and_then(Value::as_str).unwrap_or("custom");
+                let severity = match rule.get("severity") {
+                    Some(value) => value.as_str()
+                        .and_then(Severity::parse)
+                        .ok_or_else(|| invalid(format!("Unknown severity for pattern `{}`", pattern)))?,
+                    None => table.get("categories")
+                        .and_then(|c| c.get(category))
+                        .and_then(Value::as_str)
+                        .and_then(Severity::parse)
+                        .unwrap_or_else(|| default_severity(category)),
+                };
+                let rule = ThreatRule { pattern: pattern.to_string(), category: category.to_string(), severity };
+                match self.rules.iter_mut().find(|r| r.pattern == rule.pattern) {
+                    Some(existing) => *existing = rule,
+                    None => self.rules.push(rule),
+                }
+            }
+        }
+
+        if let Some(imports) = table.get("imports") {
+            self.imports = Some(ImportPolicy::parse(imports).map_err(invalid)?);
+        }
+        Ok(())
+    }
+}
+
+fn string_list(value: Option<&Value>, name: &str) -> std::result::Result<Vec<String>, String> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    value.as_array()
+        .and_then(|items| items.iter().map(|i| i.as_str().map(str::to_string)).collect())
+        .ok_or_else(|| format!("`{}` must be a list of strings", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn applied(document: Value) -> ThreatPolicy {
+        let mut policy = ThreatPolicy::builtin();
+        policy.apply(&document, "policy").unwrap();
+        policy
+    }
+
+    fn rule<'a>(policy: &'a ThreatPolicy, pattern: &str) -> Option<&'a ThreatRule> {
+        policy.rules().iter().find(|r| r.pattern == pattern)
+    }
+
+    #[test]
+    fn builtin_rules_have_category_severities() {
+        let policy = ThreatPolicy::builtin();
+        assert_eq!(rule(&policy, "eval(").map(|r| r.severity), Some(Severity::Critical));
+        assert_eq!(rule(&policy, "open(").map(|r| r.severity), Some(Severity::High));
+        assert_eq!(rule(&policy, "input(").map(|r| (r.category.as_str(), r.severity)), Some(("interactive", Severity::Low)));
+        assert_eq!(policy.imports(), None);
+    }
+
+    #[test]
+    fn rules_are_removed_by_pattern_and_category() {
+        let policy = applied(json!({ "remove": ["input("], "remove_categories": ["introspection"] }));
+        assert!(rule(&policy, "input(").is_none());
+        assert!(rule(&policy, "raw_input(").is_some());
+        assert!(policy.rules().iter().all(|r| r.category != "introspection"));
+    }
+
+    #[test]
+    fn categories_are_rerated() {
+        let policy = applied(json!({ "categories": { "filesystem": "CRITICAL" } }));
+        assert!(policy.rules().iter().filter(|r| r.category == "filesystem").all(|r| r.severity == Severity::Critical));
+    }
+
+    #[test]
+    fn rules_are_added_or_replaced() {
+        let policy = applied(json!({
+            "categories": { "network": "low" },
+            "rules": [
+                { "pattern": "socket.", "category": "network" },
+                { "pattern": "pickle.loads(" },
+                { "pattern": "eval(", "category": "code-execution", "severity": "medium" },
+            ],
+        }));
+        assert_eq!(rule(&policy, "socket.").map(|r| r.severity), Some(Severity::Low));
+        assert_eq!(rule(&policy, "pickle.loads(").map(|r| (r.category.as_str(), r.severity)), Some(("custom", Severity::Medium)));
+        assert_eq!(rule(&policy, "eval(").map(|r| r.severity), Some(Severity::Medium));
+        assert_eq!(policy.rules().iter().filter(|r| r.pattern == "eval(").count(), 1);
+    }
+
+    #[test]
+    fn replace_starts_from_no_rules() {
+        let policy = applied(json!({ "replace": true, "rules": [{ "pattern": "x(" }] }));
+        assert_eq!(policy.rules().len(), 1);
+    }
+
+    #[test]
+    fn invalid_documents_are_refused() {
+        for document in [
+            json!([]),
+            json!({ "remove": "eval(" }),
+            json!({ "categories": { "filesystem": "severe" } }),
+            json!({ "rules": [{ "pattern": "" }] }),
+            json!({ "rules": [{ "pattern": "x(", "severity": 3 }] }),
+            json!({ "imports": { "action": "ignore" } }),
+        ] {
+            let mut policy = ThreatPolicy::builtin();
+            assert!(matches!(policy.apply(&document, "policy"), Err(Error::Document { .. })), "{}", document);
+        }
+    }
+
+    #[test]
+    fn module_patterns_cover_submodules() {
+        assert!(module_matches("os", "os"));
+        assert!(module_matches("os", "os.path"));
+        assert!(!module_matches("os", "oss"));
+        assert!(!module_matches("os.path", "os"));
+        assert!(module_matches("xml.*", "xml.etree.ElementTree"));
+        assert!(!module_matches("xml.*", "xml"));
+        assert!(module_matches("*.request", "urllib.request"));
+        assert!(!module_matches("*.request", "urllib.parse"));
+        assert!(module_matches("*", "anything.at.all"));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let policy = applied(json!({ "imports": { "allow": ["os", "json"], "deny": ["os.system"] } })).imports().cloned().unwrap();
+        assert_eq!(policy.check("json"), None);
+        assert_eq!(policy.check("os.path"), None);
+        assert_eq!(policy.check("os.system").map(|r| r.pattern), Some("os.system".to_string()));
+        // Missing from the allow list: an empty pattern.
+        assert_eq!(policy.check("socket").map(|r| r.pattern), Some(String::new()));
+    }
+
+    #[test]
+    fn import_policies_parse_action_and_severity() {
+        let policy = applied(json!({ "imports": { "deny": ["ctypes"] } })).imports().cloned().unwrap();
+        assert_eq!(policy, ImportPolicy::deny(vec!["ctypes".to_string()]));
+        assert_eq!(policy.check("ctypes").map(|r| (r.category, r.severity)), Some(("import".to_string(), Severity::High)));
+
+        let policy = applied(json!({ "imports": { "allow": [], "action": "warn", "severity": "low" } })).imports().cloned().unwrap();
+        assert_eq!(policy, ImportPolicy::allow(Vec::new()).with_action(ImportAction::Warn).with_severity(Severity::Low));
+        assert!(policy.check("json").is_some());
+        // Without `allow` everything not denied passes.
+        assert_eq!(ImportPolicy::deny(Vec::new()).check("json"), None);
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::Error;
+use crate::dialect;
+use crate::mapping_file::{self, MappingLayer, MappingTable};
+use crate::matcher::MatchEngine;
+use crate::normalize::{Confusables, Normalization};
+use crate::policy::ThreatPolicy;
+use crate::threat_detector::{DetectorBackend, ThreatDetector};
+use crate::transpiler::SymbolTranspiler;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+create_exception!(phirust_transpiler, TranspilerError, PyException);
+create_exception!(phirust_transpiler, ConfigurationError, TranspilerError);
+create_exception!(phirust_transpiler, SecurityError, TranspilerError);
+
+#[pyclass(name = "ThreatDetector", frozen)]
+struct PyThreatDetector {
+    inner: Arc<ThreatDetector>,
+}
+
+#[pymethods]
+impl PyThreatDetector {
+    #[new]
+    #[pyo3(signature = (policy = None, backend = "substring"))]
+    fn new(policy: Option<std::path::PathBuf>, backend: &str) -> PyResult<Self> {
+        let backend = match backend {
+            "substring" => DetectorBackend::Substring,
+            "ast" => DetectorBackend::Ast,
+            other => return Err(ConfigurationError::new_err(format!("Unknown detector backend `{}`", other))),
+        };
+        let policy = match policy {
+            Some(path) => ThreatPolicy::load(&path).map_err(to_py_err)?,
+            None => ThreatPolicy::builtin(),
+        };
+        let inner = ThreatDetector::with_backend(&policy, backend).map_err(to_py_err)?;
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    fn is_dangerous(&self, py: Python<'_>, python_code: &str) -> bool {
+        let detector = &self.inner;
+        py.detach(|| detector.is_dangerous(python_code))
+    }
+}
+
+/// A confusables table, or `builtin` or a path as accepted by [`Confusables::resolve`].
+#[derive(FromPyObject)]
+enum ConfusablesArg {
+    Name(String),
+    Table(HashMap<String, String>),
+}
+
+// Frozen so calls share the object; the lock lets `transpile` run without
+// the GIL while calls on the same object wait their turn.
+#[pyclass(name = "SymbolTranspiler", frozen)]
+struct PySymbolTranspiler {
+    inner: Mutex<SymbolTranspiler>,
+    threat_detector: Arc<ThreatDetector>,
+}
+
+impl PySymbolTranspiler {
+    fn lock(&self) -> MutexGuard<'_, SymbolTranspiler> {
+        // A panic mid-call leaves no half-applied state worth refusing over.
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[pymethods]
+impl PySymbolTranspiler {
+    #[new]
+    #[pyo3(signature = (threat_detector = None, engine = "regex"))]
+    fn new(threat_detector: Option<&PyThreatDetector>, engine: &str) -> PyResult<Self> {
+        let engine = match engine {
+            "regex" => MatchEngine::Regex,
+            "aho-corasick" => MatchEngine::AhoCorasick,
+            other => return Err(ConfigurationError::new_err(format!("Unknown match engine `{}`", other))),
+        };
+        let threat_detector = match threat_detector {
+            Some(detector) => detector.inner.clone(),
+            None => Arc::new(ThreatDetector::new().map_err(to_py_err)?),
+        };
+        let mut inner = SymbolTranspiler::new();
+        inner.set_engine(engine).map_err(to_py_err)?;
+        Ok(Self { inner: Mutex::new(inner), threat_detector })
+    }
+
+    #[pyo3(signature = (mappings = None, trusted = None, dialect = None, infix = None, normalize = None, confusables = None))]
+    fn configure(
+        &self,
+        mappings: Option<HashMap<String, String>>,
+        trusted: Option<Vec<String>>,
+        dialect: Option<&str>,
+        infix: Option<Vec<String>>,
+        normalize: Option<&str>,
+        confusables: Option<ConfusablesArg>,
+    ) -> PyResult<()> {
+        let form = match normalize {
+            None => None,
+            Some("none") => Some(Normalization::None),
+            Some("nfc") => Some(Normalization::Nfc),
+            Some("nfkc") => Some(Normalization::Nfkc),
+            Some(other) => return Err(ConfigurationError::new_err(format!("Unknown normalization `{}`", other))),
+        };
+        let confusables = match confusables {
+            None => None,
+            Some(ConfusablesArg::Name(name)) => Some(Confusables::resolve(&name).map_err(to_py_err)?),
+            Some(ConfusablesArg::Table(map)) => Some(Confusables { map: map.into_iter().collect() }),
+        };
+        let base = match dialect {
+            Some(name) => dialect::resolve(name).map_err(to_py_err)?.table,
+            None => MappingTable::default(),
+        };
+        let layer = MappingTable { mappings: mappings.unwrap_or_default().into_iter().collect(), ..Default::default() };
+        let layers = vec![MappingLayer::Table { table: layer, origin: "configure".to_string() }];
+        let (mut table, _) = mapping_file::layer_mappings(base, layers).map_err(to_py_err)?;
+        table.trusted.extend(trusted.unwrap_or_default());
+        table.infix.extend(infix.unwrap_or_default());
+
+        // Waiting keeps the GIL, which is safe: `transpile` unlocks before it
+        // takes the GIL back.
+        let mut inner = self.lock();
+        if let Some(form) = form {
+            inner.set_normalization(form).map_err(to_py_err)?;
+        }
+        if let Some(confusables) = confusables {
+            inner.set_confusables(confusables).map_err(to_py_err)?;
+        }
+        inner.configure_table(table).map_err(to_py_err)
+    }
+
+    #[pyo3(signature = (source, bypass_security = false))]
+    fn transpile(&self, py: Python<'_>, source: &str, bypass_security: bool) -> PyResult<String> {
+        py.detach(|| self.lock().transpile(source, &self.threat_detector, bypass_security))
+            .map_err(to_py_err)
+    }
+}
+
+fn to_py_err(error: Error) -> PyErr {
+    match error {
+        Error::Security(_) => SecurityError::new_err(error.to_string()),
+        _ => ConfigurationError::new_err(error.to_string()),
+    }
+}
+
+/// Names of the built-in dialects.
+#[pyfunction]
+fn dialects() -> Vec<&'static str> {
+    dialect::builtin().iter().map(|d| d.name).collect()
+}
+
+#[pymodule]
+fn phirust_transpiler(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_function(wrap_pyfunction!(dialects, m)?)?;
+    m.add_class::<PySymbolTranspiler>()?;
+    m.add_class::<PyThreatDetector>()?;
+    m.add("TranspilerError", py.get_type::<TranspilerError>())?;
+    m.add("ConfigurationError", py.get_type::<ConfigurationError>())?;
+    m.add("SecurityError", py.get_type::<SecurityError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    // Runs `code` with the module imported as `m`.
+    fn run(code: &std::ffi::CStr) -> PyResult<()> {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "phirust_transpiler")?;
+            phirust_transpiler(&module)?;
+            let globals = PyDict::new(py);
+            globals.set_item("m", module)?;
+            py.run(code, Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn transpiles_with_a_dialect() {
+        run(c"
+t = m.SymbolTranspiler(engine='aho-corasick')
+t.configure(mappings={'π': 'log'}, dialect='phicode')
+assert t.transpile('ƒ f(): π(1)') == 'def f(): log(1)'
+assert 'phicode' in m.dialects()
+").unwrap();
+    }
+
+    #[test]
+    fn errors_are_typed() {
+        run(c"
+t = m.SymbolTranspiler()
+t.configure(mappings={'∂': 'eval('})
+try:
+    t.transpile('∂1)')
+    raise AssertionError('not blocked')
+except m.SecurityError as e:
+    assert isinstance(e, m.TranspilerError)
+assert t.transpile('∂1)', bypass_security=True) == 'eval(1)'
+for bad in (lambda: m.SymbolTranspiler(engine='sed'), lambda: t.configure(normalize='nfd')):
+    try:
+        bad()
+        raise AssertionError('accepted')
+    except m.ConfigurationError:
+        pass
+").unwrap();
+    }
+
+    #[test]
+    fn detectors_are_shared() {
+        run(c"
+d = m.ThreatDetector(backend='ast')
+assert d.is_dangerous('eval(x)')
+assert not d.is_dangerous('my_eval(x)')
+t = m.SymbolTranspiler(d)
+t.configure(mappings={'∂': 'eval('})
+try:
+    t.transpile('∂x)')
+    raise AssertionError('not blocked')
+except m.SecurityError:
+    pass
+").unwrap();
+    }
+
+    #[test]
+    fn calls_on_one_object_run_concurrently() {
+        run(c"
+import threading
+t = m.SymbolTranspiler()
+t.configure(dialect='phicode')
+source = 'ƒ f(): pass\\n' * 20000
+results, errors = [], []
+def work():
+    try:
+        results.append(t.transpile(source))
+    except Exception as e:
+        errors.append(e)
+threads = [threading.Thread(target=work) for _ in range(8)]
+for thread in threads:
+    thread.start()
+for thread in threads:
+    thread.join()
+assert not errors, errors
+assert results == ['def f(): pass\\n' * 20000] * 8
+").unwrap();
+    }
+}
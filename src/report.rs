@@ -0,0 +1,172 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{SecurityViolation, Stage};
+use crate::policy::Severity;
+use serde_json::{json, Value};
+
+const REPLACEMENT_RULE: &str = "phicode/dangerous-replacement";
+const OUTPUT_RULE: &str = "phicode/dangerous-output";
+const IMPORT_RULE: &str = "phicode/disallowed-import";
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low => "note",
+    }
+}
+
+fn rule_id(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Replacement => REPLACEMENT_RULE,
+        Stage::Output => OUTPUT_RULE,
+        Stage::Import => IMPORT_RULE,
+    }
+}
+
+/// Serialises findings as a JSON document of the form `{"source", "findings": [...]}`.
+pub fn to_json(findings: &[SecurityViolation], source_name: &str) -> String {
+    let findings: Vec<Value> = findings.iter()
+        .map(|f| json!({
+            "rule": rule_id(f.stage),
+            "symbol": f.symbol,
+            "replacement": f.replacement,
+            "pattern": f.pattern,
+            "category": f.category,
+            "severity": f.severity.as_str(),
+            "offset": f.offset,
+            "line": f.line,
+            "column": f.column,
+        }))
+        .collect();
+    serde_json::to_string_pretty(&json!({ "source": source_name, "findings": findings }))
+        .unwrap_or_default()
+}
+
+/// Serialises findings as a SARIF 2.1.0 log with a single run.
+pub fn to_sarif(findings: &[SecurityViolation], source_name: &str) -> String {
+    let results: Vec<Value> = findings.iter()
+        .map(|f| json!({
+            "ruleId": rule_id(f.stage),
+            "level": sarif_level(f.severity),
+            "properties": { "category": f.category, "severity": f.severity.as_str() },
+            "message": { "text": f.message() },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": source_name },
+                    "region": {
+                        "startLine": f.line,
+                        "startColumn": f.column,
+                        "endColumn": f.column + f.symbol.lines().next().unwrap_or("").chars().count(),
+                    },
+                },
+            }],
+        }))
+        .collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "phirust-transpiler",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        {
+                            "id": REPLACEMENT_RULE,
+                            "shortDescription": { "text": "Symbol expands to a dangerous Python construct" },
+                            "defaultConfiguration": { "level": "error" },
+                        },
+                        {
+                            "id": OUTPUT_RULE,
+                            "shortDescription": { "text": "Transpiled output contains a dangerous Python construct" },
+                            "defaultConfiguration": { "level": "error" },
+                        },
+                        {
+                            "id": IMPORT_RULE,
+                            "shortDescription": { "text": "Transpiled output imports a module the import policy refuses" },
+                            "defaultConfiguration": { "level": "error" },
+                        },
+                    ],
+                },
+            },
+            // Columns count characters, not SARIF's default UTF-16 units.
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::ThreatRule;
+
+    // One finding of each kind, the replacement one past a two-unit `𝑓`.
+    fn findings() -> Vec<SecurityViolation> {
+        let rule = |pattern: &str, category: &str, severity| ThreatRule { pattern: pattern.to_string(), category: category.to_string(), severity };
+        vec![
+            SecurityViolation::new(Stage::Replacement, "∂".to_string(), "eval(".to_string(), &rule("eval(", "code-execution", Severity::Critical), 9, 1, 5),
+            SecurityViolation::new(Stage::Output, "open(".to_string(), "open(".to_string(), &rule("open(", "filesystem", Severity::Medium), 20, 2, 1),
+            SecurityViolation::new(Stage::Import, "import os".to_string(), "os".to_string(), &rule("os", "imports", Severity::Low), 30, 3, 1),
+        ]
+    }
+
+    #[test]
+    fn json_lists_every_finding() {
+        let report: Value = serde_json::from_str(&to_json(&findings(), "a.φ")).unwrap();
+        assert_eq!(report, json!({
+            "source": "a.φ",
+            "findings": [
+                { "rule": REPLACEMENT_RULE, "symbol": "∂", "replacement": "eval(", "pattern": "eval(", "category": "code-execution",
+                  "severity": "critical", "offset": 9, "line": 1, "column": 5 },
+                { "rule": OUTPUT_RULE, "symbol": "open(", "replacement": "open(", "pattern": "open(", "category": "filesystem",
+                  "severity": "medium", "offset": 20, "line": 2, "column": 1 },
+                { "rule": IMPORT_RULE, "symbol": "import os", "replacement": "os", "pattern": "os", "category": "imports",
+                  "severity": "low", "offset": 30, "line": 3, "column": 1 },
+            ],
+        }));
+    }
+
+    #[test]
+    fn sarif_locates_every_finding() {
+        let log: Value = serde_json::from_str(&to_sarif(&findings(), "a.φ")).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        let rules: Vec<&Value> = run["tool"]["driver"]["rules"].as_array().unwrap().iter().map(|r| &r["id"]).collect();
+        assert_eq!(rules, [REPLACEMENT_RULE, OUTPUT_RULE, IMPORT_RULE]);
+
+        let result = |rule: &str, level: &str, category: &str, severity: &str, message: &str, line: usize, start: usize, end: usize| json!({
+            "ruleId": rule,
+            "level": level,
+            "properties": { "category": category, "severity": severity },
+            "message": { "text": message },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "a.φ" },
+                    "region": { "startLine": line, "startColumn": start, "endColumn": end },
+                },
+            }],
+        });
+        assert_eq!(run["results"], json!([
+            result(REPLACEMENT_RULE, "error", "code-execution", "critical",
+                "`∂` expands to `eval(`, matching code-execution threat pattern `eval(`", 1, 5, 6),
+            result(OUTPUT_RULE, "warning", "filesystem", "medium",
+                "transpiled code `open(` matches filesystem threat pattern `open(`", 2, 1, 6),
+            result(IMPORT_RULE, "note", "imports", "low",
+                "import of `os` is denied by import pattern `os`", 3, 1, 10),
+        ]));
+    }
+
+    #[test]
+    fn empty_reports_are_valid() {
+        let report: Value = serde_json::from_str(&to_json(&[], "a.φ")).unwrap();
+        assert_eq!(report, json!({ "source": "a.φ", "findings": [] }));
+        let log: Value = serde_json::from_str(&to_sarif(&[], "a.φ")).unwrap();
+        assert_eq!(log["runs"][0]["results"], json!([]));
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::Result;
+use crate::lexer;
+use crate::matcher::{MatchEngine, Needle, SymbolMatcher};
+use ahash::{AHashMap, AHashSet};
+
+/// A Python replacement produced by several symbols; `chosen` is used in reverse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousMapping {
+    pub python: String,
+    pub chosen: String,
+    pub alternatives: Vec<String>,
+}
+
+/// A reverse replacement that would not survive transpiling forward again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundTripIssue {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Python-to-symbol lookup built from a forward mapping table.
+#[derive(Clone)]
+pub struct ReverseTable {
+    symbols: AHashMap<String, String>,
+    matcher: Option<SymbolMatcher>,
+    ambiguous: Vec<AmbiguousMapping>,
+}
+
+impl ReverseTable {
+    /// Inverts `mappings`, choosing one symbol per Python replacement, and
+    /// compiles the Python side for `engine`.
+    pub fn new(mappings: &AHashMap<String, String>, engine: MatchEngine) -> Result<Self> {
+        let mut candidates: AHashMap<&str, Vec<&str>> = AHashMap::new();
+        for (symbol, python) in mappings {
+            if !python.is_empty() {
+                candidates.entry(python.as_str()).or_default().push(symbol.as_str());
+            }
+        }
+
+        let mut symbols = AHashMap::new();
+        let mut ambiguous = Vec::new();
+        for (python, mut options) in candidates {
+            // Shortest symbol wins, ties broken lexically, so the choice never
+            // depends on hash map iteration order.
+            options.sort_by(|a, b| a.chars().count().cmp(&b.chars().count()).then(a.cmp(b)));
+            let chosen = options[0].to_string();
+            if options.len() > 1 {
+                ambiguous.push(AmbiguousMapping {
+                    python: python.to_string(),
+                    chosen: chosen.clone(),
+                    alternatives: options[1..].iter().map(|s| s.to_string()).collect(),
+                });
+            }
+            symbols.insert(python.to_string(), chosen);
+        }
+        ambiguous.sort_by(|a, b| a.python.cmp(&b.python));
+
+        if symbols.is_empty() {
+            return Ok(Self { symbols, matcher: None, ambiguous });
+        }
+
+        let needles = symbols.keys().map(|python| Needle::bounded(python)).collect();
+        let matcher = SymbolMatcher::new(needles, engine)?;
+        Ok(Self { symbols, matcher: Some(matcher), ambiguous })
+    }
+
+    /// Replacements that several symbols map to, sorted by Python text.
+    pub fn ambiguous(&self) -> &[AmbiguousMapping] {
+        &self.ambiguous
+    }
+
+    /// Rewrites Python back into symbols, then replays the forward pass over the
+    /// result to report every spot that would not come back unchanged.
+    pub fn transpile(&self, source: &str, forward: Option<&SymbolMatcher>, mappings: &AHashMap<String, String>) -> (String, Vec<RoundTripIssue>) {
+        let matcher = match &self.matcher {
+            Some(m) => m,
+            None => return (source.to_string(), Vec::new()),
+        };
+
+        let (output, inserted) = lexer::replace_in_code(matcher, source, |matched, _| {
+            self.symbols.get(matched).cloned().unwrap_or_else(|| matched.to_string())
+        });
+
+        let mut issues = Vec::new();
+        let forward = match forward {
+            Some(f) => f,
+            None => return (output, issues),
+        };
+
+        let inserted_at: AHashSet<(usize, usize)> = inserted.iter()
+            .map(|r| (r.output.start, r.output.end))
+            .collect();
+        let (restored, replayed) = lexer::replace_in_code(forward, &output, |matched, _| {
+            mappings.get(matched).cloned().unwrap_or_else(|| matched.to_string())
+        });
+
+        for replacement in &replayed {
+            if inserted_at.contains(&(replacement.source.start, replacement.source.end)) {
+                continue;
+            }
+            let text = &output[replacement.source.clone()];
+            let original = inserted.iter()
+                .find(|r| r.output.start < replacement.source.end && replacement.source.start < r.output.end)
+                .map(|r| r.source.start);
+            let reason = match original {
+                Some(_) => format!("symbol merges with adjacent text and would transpile as `{}`", text),
+                None => format!("`{}` is already a symbol and would transpile to `{}`", text, mappings.get(text).map_or("", |s| s.as_str())),
+            };
+            let offset = original.unwrap_or_else(|| output_to_source(&inserted, replacement.source.start));
+            let (line, column) = lexer::line_col(source, offset);
+            issues.push(RoundTripIssue { line, column, text: text.to_string(), reason });
+        }
+
+        if issues.is_empty() && restored != source {
+            let offset = source.bytes()
+                .zip(restored.bytes())
+                .position(|(a, b)| a != b)
+                .unwrap_or(source.len().min(restored.len()));
+            let offset = floor_char_boundary(source, offset);
+            let (line, column) = lexer::line_col(source, offset);
+            let text = source[offset..].chars().take(16).collect();
+            issues.push(RoundTripIssue { line, column, text, reason: "output differs after transpiling back".to_string() });
+        }
+
+        (output, issues)
+    }
+}
+
+fn output_to_source(inserted: &[lexer::Replacement], offset: usize) -> usize {
+    let mut shift: isize = 0;
+    for r in inserted {
+        if r.output.start >= offset {
+            break;
+        }
+        shift += r.source.len() as isize - r.output.len() as isize;
+    }
+    (offset as isize + shift) as usize
+}
+
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(pairs: &[(&str, &str)]) -> AHashMap<String, String> {
+        pairs.iter().map(|(symbol, python)| (symbol.to_string(), python.to_string())).collect()
+    }
+
+    fn reverse(pairs: &[(&str, &str)], source: &str) -> (String, Vec<RoundTripIssue>) {
+        let mappings = table(pairs);
+        let needles = mappings.keys().map(|symbol| Needle::symbol(symbol)).collect();
+        let forward = SymbolMatcher::new(needles, MatchEngine::Regex).unwrap();
+        ReverseTable::new(&mappings, MatchEngine::Regex).unwrap().transpile(source, Some(&forward), &mappings)
+    }
+
+    #[test]
+    fn python_is_rewritten_outside_literals_and_words() {
+        let (output, issues) = reverse(&[("ƒ", "def"), ("λ", "lambda")], "def f(): return lambda: 'def'  # lambda\nundefined\n");
+        assert_eq!(output, "ƒ f(): return λ: 'def'  # lambda\nundefined\n");
+        assert_eq!(issues, []);
+    }
+
+    #[test]
+    fn shortest_then_lowest_symbol_is_chosen() {
+        let mappings = table(&[("lam", "lambda"), ("λ", "lambda"), ("β", "v"), ("α", "v"), ("ƒ", "def")]);
+        let reverse = ReverseTable::new(&mappings, MatchEngine::AhoCorasick).unwrap();
+        assert_eq!(reverse.ambiguous(), [
+            AmbiguousMapping { python: "lambda".to_string(), chosen: "λ".to_string(), alternatives: vec!["lam".to_string()] },
+            AmbiguousMapping { python: "v".to_string(), chosen: "α".to_string(), alternatives: vec!["β".to_string()] },
+        ]);
+        assert_eq!(reverse.transpile("lambda v", None, &mappings).0, "λ α");
+    }
+
+    #[test]
+    fn symbols_already_in_the_source_are_reported() {
+        let (output, issues) = reverse(&[("ƒ", "def")], "x = 1\nƒ = 2\ndef f(): pass\n");
+        assert_eq!(output, "x = 1\nƒ = 2\nƒ f(): pass\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column, issues[0].text.as_str()), (2, 1, "ƒ"));
+        assert!(issues[0].reason.contains("already a symbol"));
+    }
+
+    #[test]
+    fn merged_symbols_are_reported() {
+        let (output, issues) = reverse(&[("¬", "not "), ("¬¬", "bool")], "y = not not x\n");
+        assert_eq!(output, "y = ¬¬x\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column, issues[0].text.as_str()), (1, 5, "¬¬"));
+        assert!(issues[0].reason.contains("merges"));
+    }
+}
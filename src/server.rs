@@ -0,0 +1,238 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::dialect::{self, DialectManifest};
+use crate::error::Error;
+use crate::mapping_file::{self, MappingLayer, MappingTable};
+use crate::matcher::MatchEngine;
+use crate::normalize::{Confusables, Normalization};
+use crate::policy::ThreatPolicy;
+use crate::threat_detector::{DetectorBackend, ThreatDetector};
+use crate::transpiler::{OutputScan, SymbolTranspiler};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Line-delimited JSON front-end keeping one configured transpiler alive.
+pub struct Server {
+    transpiler: SymbolTranspiler,
+    threat_detector: ThreatDetector,
+    bypass: bool,
+    /// Whether the operator allowed bypassing threat detection; clients may
+    /// only turn it on if so.
+    allow_bypass: bool,
+    dialect: Option<DialectManifest>,
+}
+
+impl Server {
+    /// Wraps an already configured transpiler; `bypass` is the default for
+    /// requests. Requests may only bypass threat detection if it is set.
+    pub fn new(transpiler: SymbolTranspiler, threat_detector: ThreatDetector, bypass: bool) -> Self {
+        Self { transpiler, threat_detector, bypass, allow_bypass: bypass, dialect: None }
+    }
+
+    /// Sets the dialect sources are checked against for version mismatches.
+    pub fn set_dialect(&mut self, dialect: Option<DialectManifest>) {
+        self.dialect = dialect;
+    }
+
+    /// One JSON request per line in, one JSON response per line out. A broken
+    /// request gets an error response; only I/O failures end the loop.
+    pub fn run<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(request) => {
+                    let id = request.get("id").cloned().unwrap_or(Value::Null);
+                    match self.handle(&request) {
+                        Ok(result) => json!({ "id": id, "result": result }),
+                        Err((code, message)) => json!({ "id": id, "error": { "code": code, "message": message } }),
+                    }
+                },
+                Err(e) => json!({ "id": null, "error": { "code": "parse_error", "message": e.to_string() } }),
+            };
+
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &Value) -> Result<Value, (&'static str, String)> {
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+        match request.get("method").and_then(Value::as_str) {
+            Some("configure") => self.configure(&params),
+            Some("transpile") => self.transpile(&params),
+            Some("check") => {
+                let code = string_param(&params, "code")?;
+                Ok(json!({ "dangerous": self.threat_detector.is_dangerous(code) }))
+            },
+            Some(other) => Err(("unknown_method", format!("Unknown method `{}`", other))),
+            None => Err(("invalid_request", "Missing `method`".to_string())),
+        }
+    }
+
+    // Every parameter is checked and the new configuration built aside, so a
+    // refused request leaves the server as it was.
+    fn configure(&mut self, params: &Value) -> Result<Value, (&'static str, String)> {
+        let configuration_error = |e: Error| ("configuration_error", e.to_string());
+        let bypass = self.bypass_param(params)?;
+        let manifest = match params.get("dialect") {
+            Some(name) => {
+                let name = name.as_str()
+                    .ok_or(("invalid_params", "`dialect` must be a name or manifest path".to_string()))?;
+                Some(dialect::resolve(name).map_err(configuration_error)?)
+            },
+            None => None,
+        };
+        let remaps = manifest.is_some() || params.get("symbols_files").is_some() || params.get("symbols").is_some();
+        let mut layers = Vec::new();
+        if let Some(files) = params.get("symbols_files").and_then(Value::as_array) {
+            for file in files {
+                let path = file.as_str()
+                    .ok_or(("invalid_params", "`symbols_files` must be strings".to_string()))?;
+                layers.push(MappingLayer::File(PathBuf::from(path)));
+            }
+        }
+        if let Some(symbols) = params.get("symbols") {
+            let table = MappingTable::from_document(symbols, "symbols")
+                .map_err(|e| ("invalid_params", e.to_string()))?;
+            layers.push(MappingLayer::Table { table, origin: "symbols".to_string() });
+        }
+        let base = manifest.as_ref().map(|m| m.table.clone()).unwrap_or_default();
+        let (mappings, overridden) = mapping_file::layer_mappings(base, layers).map_err(configuration_error)?;
+
+        let policy = params.get("threat_policy");
+        let backend = params.get("detector");
+        let threat_detector = if policy.is_some() || backend.is_some() {
+            let policy = match policy {
+                Some(path) => {
+                    let path = path.as_str()
+                        .ok_or(("invalid_params", "`threat_policy` must be a path".to_string()))?;
+                    ThreatPolicy::load(Path::new(path)).map_err(configuration_error)?
+                },
+                None => ThreatPolicy::from_rules(self.threat_detector.rules().to_vec()),
+            };
+            let backend = match backend.map(Value::as_str) {
+                Some(Some("substring")) => DetectorBackend::Substring,
+                Some(Some("ast")) => DetectorBackend::Ast,
+                Some(_) => return Err(("invalid_params", "`detector` must be substring or ast".to_string())),
+                None => self.threat_detector.backend(),
+            };
+            Some(ThreatDetector::with_backend(&policy, backend).map_err(configuration_error)?)
+        } else {
+            None
+        };
+        let scan = match params.get("scan_output").map(Value::as_str) {
+            None => None,
+            Some(Some("off")) => Some(OutputScan::Off),
+            Some(Some("warn")) => Some(OutputScan::Warn),
+            Some(Some("block")) => Some(OutputScan::Block),
+            Some(_) => return Err(("invalid_params", "`scan_output` must be off, warn or block".to_string())),
+        };
+        let engine = match params.get("engine").map(Value::as_str) {
+            None => None,
+            Some(Some("regex")) => Some(MatchEngine::Regex),
+            Some(Some("aho-corasick")) => Some(MatchEngine::AhoCorasick),
+            Some(_) => return Err(("invalid_params", "`engine` must be regex or aho-corasick".to_string())),
+        };
+        let form = match params.get("normalize").map(Value::as_str) {
+            None => None,
+            Some(Some("none")) => Some(Normalization::None),
+            Some(Some("nfc")) => Some(Normalization::Nfc),
+            Some(Some("nfkc")) => Some(Normalization::Nfkc),
+            Some(_) => return Err(("invalid_params", "`normalize` must be none, nfc or nfkc".to_string())),
+        };
+        let confusables = match params.get("confusables") {
+            None => None,
+            Some(Value::String(name)) => Some(Confusables::resolve(name).map_err(configuration_error)?),
+            Some(table) => Some(Confusables::from_document(table, "confusables").map_err(|e| ("invalid_params", e.to_string()))?),
+        };
+
+        let mut transpiler = self.transpiler.clone();
+        if let Some(scan) = scan {
+            transpiler.set_output_scan(scan);
+        }
+        if let Some(engine) = engine {
+            transpiler.set_engine(engine).map_err(configuration_error)?;
+        }
+        if let Some(form) = form {
+            transpiler.set_normalization(form).map_err(configuration_error)?;
+        }
+        if let Some(confusables) = confusables {
+            transpiler.set_confusables(confusables).map_err(configuration_error)?;
+        }
+        // Settings alone keep the mappings and dialect configured so far.
+        if remaps {
+            transpiler.configure_table(mappings).map_err(configuration_error)?;
+        }
+
+        self.transpiler = transpiler;
+        if let Some(threat_detector) = threat_detector {
+            self.threat_detector = threat_detector;
+        }
+        if let Some(bypass) = bypass {
+            self.bypass = bypass;
+        }
+        if remaps {
+            self.dialect = manifest;
+        }
+        let count = self.transpiler.mappings().len();
+        let trusted = self.transpiler.trusted().len();
+        let overridden: Vec<Value> = overridden.iter()
+            .map(|k| json!({ "symbol": k.symbol, "previous": k.previous, "replacement": k.replacement, "origin": k.origin }))
+            .collect();
+        let issues: Vec<Value> = self.transpiler.validate(Some(&self.threat_detector)).iter()
+            .map(|i| json!({ "symbol": i.symbol, "message": i.message() }))
+            .collect();
+        Ok(json!({ "symbols": count, "trusted": trusted, "overridden": overridden, "issues": issues }))
+    }
+
+    fn transpile(&mut self, params: &Value) -> Result<Value, (&'static str, String)> {
+        let source = string_param(params, "source")?;
+        let bypass = self.bypass_param(params)?.unwrap_or(self.bypass);
+        let output = self.transpiler.transpile(source, &self.threat_detector, bypass)
+            .map_err(|e| (error_code(&e), e.to_string()))?;
+        let mut warnings: Vec<Value> = self.dialect.as_ref()
+            .and_then(|d| d.check(source))
+            .map(|m| json!({ "message": m.to_string(), "line": m.declared.line, "column": 1 }))
+            .into_iter()
+            .collect();
+        warnings.extend(self.transpiler.take_warnings().iter()
+            .map(|w| json!({ "message": w.message(), "line": w.line, "column": w.column })));
+        warnings.extend(self.transpiler.take_confusables().iter()
+            .map(|c| json!({ "message": c.to_string(), "line": c.line, "column": c.column })));
+        let exemptions: Vec<Value> = self.transpiler.take_exemptions().iter()
+            .map(|e| json!({ "symbol": e.symbol, "message": e.message(), "line": e.line, "column": e.column }))
+            .collect();
+        Ok(json!({ "output": output, "warnings": warnings, "exemptions": exemptions }))
+    }
+
+    fn bypass_param(&self, params: &Value) -> Result<Option<bool>, (&'static str, String)> {
+        match params.get("bypass") {
+            None => Ok(None),
+            Some(Value::Bool(true)) if !self.allow_bypass => {
+                Err(("forbidden", "Bypassing threat detection requires starting the server with --bypass".to_string()))
+            },
+            Some(Value::Bool(bypass)) => Ok(Some(*bypass)),
+            Some(_) => Err(("invalid_params", "`bypass` must be a boolean".to_string())),
+        }
+    }
+}
+
+fn error_code(error: &Error) -> &'static str {
+    match error {
+        Error::Security(_) => "security_error",
+        _ => "configuration_error",
+    }
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, (&'static str, String)> {
+    params.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ("invalid_params", format!("Missing string parameter `{}`", name)))
+}
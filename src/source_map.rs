@@ -0,0 +1,190 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::lexer::{self, Replacement};
+use serde_json::json;
+
+/// A byte offset with its one-based line and character column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub byte: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Where a replacement started in the source and in the generated output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionMapping {
+    pub original: Position,
+    pub generated: Position,
+}
+
+/// Position map between PhiCode source and transpiled Python.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    pub mappings: Vec<PositionMapping>,
+    replacements: Vec<Replacement>,
+    source_lines: Vec<usize>,
+    output_lines: Vec<usize>,
+}
+
+impl SourceMap {
+    /// Builds the map from the replacements recorded while transpiling.
+    pub fn new(source: &str, output: &str, replacements: &[Replacement]) -> Self {
+        let source_lines = line_starts(source);
+        let output_lines = line_starts(output);
+
+        let mut mappings = Vec::with_capacity(replacements.len());
+        for r in replacements {
+            mappings.push(PositionMapping {
+                original: position(source, &source_lines, r.source.start),
+                generated: position(output, &output_lines, r.output.start),
+            });
+        }
+
+        Self { mappings, replacements: replacements.to_vec(), source_lines, output_lines }
+    }
+
+    /// Encodes a Source Map v3 document. Every generated line gets a segment
+    /// at its start, plus one at each replacement start and end. Columns are
+    /// counted in UTF-16 code units, as the format requires.
+    pub fn to_v3_json(&self, source: &str, output: &str, file: &str, source_name: &str) -> String {
+        let mut points: Vec<usize> = self.output_lines.clone();
+        points.extend(self.replacements.iter().flat_map(|r| [r.output.start, r.output.end]));
+        points.sort_unstable();
+        points.dedup();
+
+        let mut encoded = String::new();
+        let mut current_line = 0;
+        let mut previous_column = 0i64;
+        let mut previous_original_line = 0i64;
+        let mut previous_original_column = 0i64;
+        let mut first_in_line = true;
+
+        for generated in points {
+            if generated >= output.len() && !output.is_empty() {
+                continue;
+            }
+            let gen_pos = position(output, &self.output_lines, generated);
+            while current_line < gen_pos.line - 1 {
+                encoded.push(';');
+                current_line += 1;
+                previous_column = 0;
+                first_in_line = true;
+            }
+            let original_offset = lexer::source_offset(&self.replacements, generated);
+            let original = position(source, &self.source_lines, original_offset);
+
+            if !first_in_line {
+                encoded.push(',');
+            }
+            first_in_line = false;
+
+            let column = utf16_column(output, &self.output_lines, generated);
+            let original_line = original.line as i64 - 1;
+            let original_column = utf16_column(source, &self.source_lines, original_offset);
+            encode_vlq(&mut encoded, column - previous_column);
+            encode_vlq(&mut encoded, 0);
+            encode_vlq(&mut encoded, original_line - previous_original_line);
+            encode_vlq(&mut encoded, original_column - previous_original_column);
+            previous_column = column;
+            previous_original_line = original_line;
+            previous_original_column = original_column;
+        }
+
+        json!({
+            "version": 3,
+            "file": file,
+            "sources": [source_name],
+            "names": [],
+            "mappings": encoded,
+        }).to_string()
+    }
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(i, _)| i + 1).filter(|&i| i < text.len()));
+    starts
+}
+
+fn position(text: &str, lines: &[usize], byte: usize) -> Position {
+    let line = lines.partition_point(|&start| start <= byte);
+    let line_start = lines[line - 1];
+    Position { byte, line, column: text[line_start..byte].chars().count() + 1 }
+}
+
+// The zero-based column of `byte` in UTF-16 code units.
+fn utf16_column(text: &str, lines: &[usize], byte: usize) -> i64 {
+    let line_start = lines[lines.partition_point(|&start| start <= byte) - 1];
+    text[line_start..byte].encode_utf16().count() as i64
+}
+
+fn encode_vlq(out: &mut String, value: i64) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut vlq = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = vlq & 0b11111;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vlq(value: i64) -> String {
+        let mut out = String::new();
+        encode_vlq(&mut out, value);
+        out
+    }
+
+    fn mappings(json: &str) -> String {
+        serde_json::from_str::<serde_json::Value>(json).unwrap()["mappings"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn vlq_encodes_signed_base64_digits() {
+        assert_eq!(vlq(0), "A");
+        assert_eq!(vlq(1), "C");
+        assert_eq!(vlq(-1), "D");
+        assert_eq!(vlq(15), "e");
+        assert_eq!(vlq(16), "gB");
+        assert_eq!(vlq(-16), "hB");
+        assert_eq!(vlq(123), "2H");
+        assert_eq!(vlq(-1000), "x+B");
+    }
+
+    #[test]
+    fn segments_follow_lines_and_replacements() {
+        // `λ` on the second line becomes `lambda`.
+        let source = "x\ny = λ: 1";
+        let output = "x\ny = lambda: 1";
+        let replacements = [Replacement { source: 6..8, output: 6..12 }];
+        let map = SourceMap::new(source, output, &replacements);
+        assert_eq!(map.mappings, [PositionMapping {
+            original: Position { byte: 6, line: 2, column: 5 },
+            generated: Position { byte: 6, line: 2, column: 5 },
+        }]);
+        // Line 2: its start, the replacement, then the text after it.
+        assert_eq!(mappings(&map.to_v3_json(source, output, "out.py", "in.φ")), "AAAA;AACA,IAAI,MAAC");
+    }
+
+    #[test]
+    fn columns_count_utf16_code_units() {
+        // `𝑓` takes two UTF-16 code units, so `λ` starts at column 5, not 4.
+        let source = "𝑓 = λ";
+        let output = "𝑓 = lambda";
+        let replacements = [Replacement { source: 7..9, output: 7..13 }];
+        let map = SourceMap::new(source, output, &replacements);
+        assert_eq!(map.mappings[0].original.column, 5);
+        assert_eq!(mappings(&map.to_v3_json(source, output, "out.py", "in.φ")), "AAAA,KAAK");
+    }
+}
@@ -1,29 +1,236 @@
 // Copyright 2025 Baleine Jay
 // Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
 // Commercial use requires a paid license. See link for details.
+use crate::ast_detector::{self, NameRules};
+use crate::error::{Error, Result};
+use crate::lexer::{self, SegmentKind};
+use crate::policy::{ImportPolicy, ThreatPolicy, ThreatRule};
 use aho_corasick::AhoCorasick;
+use std::ops::Range;
 
+/// How a [`ThreatDetector`] matches its rules against Python code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DetectorBackend {
+    /// Aho-Corasick substring search over the raw text. Fast, but blind to
+    /// strings, aliases and imports.
+    #[default]
+    Substring,
+    /// Tokenises the code and flags calls, attribute accesses and imports by
+    /// resolved name. Rules that are not plain names keep substring matching.
+    Ast,
+}
+
+/// Flags Python snippets containing dangerous constructs such as `eval(`.
+///
+/// Substring patterns are matched with whitespace, line continuations and
+/// comments removed from both pattern and code, so `eval(` also catches
+/// `eval  (` and a name split from its parenthesis by a tab, a newline, a
+/// backslash continuation or a comment.
 pub struct ThreatDetector {
     detector: AhoCorasick,
+    substring_rules: Vec<usize>,
+    names: Option<NameRules>,
+    rules: Vec<ThreatRule>,
+    imports: Option<ImportPolicy>,
 }
 
 impl ThreatDetector {
-    pub fn new() -> Result<Self, String> {
-        let threats = [
-            "eval(", "eval (", "exec(", "exec (", "compile(", "compile (",
-            "getattr(__builtins__", "getattr(__builtins__,", "globals(", "globals (",
-            "locals(", "locals (", "os.system(", "os.system (", "subprocess.",
-            "__import__", "vars(", "vars (", "dir(", "dir (", "open(", "open (",
-            "input(", "raw_input(",
-        ];
+    /// Builds the detector over the built-in threat patterns.
+    pub fn new() -> Result<Self> {
+        Self::with_policy(&ThreatPolicy::builtin())
+    }
 
+    /// Builds the detector over the rules of `policy`.
+    pub fn with_policy(policy: &ThreatPolicy) -> Result<Self> {
+        Self::with_backend(policy, DetectorBackend::Substring)
+    }
+
+    /// Builds the detector over the rules of `policy` using `backend`.
+    pub fn with_backend(policy: &ThreatPolicy, backend: DetectorBackend) -> Result<Self> {
+        let rules = policy.rules().to_vec();
+        let (names, substring_rules) = match backend {
+            DetectorBackend::Substring => (None, (0..rules.len()).collect()),
+            DetectorBackend::Ast => {
+                let (names, leftover) = NameRules::new(&rules);
+                (Some(names), leftover)
+            },
+        };
+        let patterns = substring_rules.iter().map(|&i| {
+            let normalized = Normalized::new(&rules[i].pattern).text;
+            if normalized.is_empty() { rules[i].pattern.clone() } else { normalized }
+        });
         Ok(Self {
-            detector: AhoCorasick::new(threats)
-                .map_err(|e| format!("Threat detector: {}", e))?
+            detector: AhoCorasick::new(patterns)
+                .map_err(|e| Error::ThreatDetector(e.to_string()))?,
+            substring_rules,
+            names,
+            rules,
+            imports: policy.imports().cloned(),
         })
     }
 
+    pub fn backend(&self) -> DetectorBackend {
+        match self.names {
+            Some(_) => DetectorBackend::Ast,
+            None => DetectorBackend::Substring,
+        }
+    }
+
+    /// Returns true if `python_code` contains any threat pattern.
     pub fn is_dangerous(&self, python_code: &str) -> bool {
-        self.detector.is_match(python_code)
+        match &self.names {
+            Some(_) => self.find_threat(python_code).is_some(),
+            None => self.detector.is_match(&Normalized::new(python_code).text),
+        }
+    }
+
+    /// Every non-overlapping threat in `python_code` with its byte range, in order.
+    pub fn find_threats<'a>(&'a self, python_code: &str) -> impl Iterator<Item = (Range<usize>, &'a ThreatRule)> + use<'a> {
+        let normalized = Normalized::new(python_code);
+        let mut found: Vec<(Range<usize>, usize)> = self.detector.find_iter(&normalized.text)
+            .map(|m| (normalized.original(python_code, m.range()), self.substring_rules[m.pattern().as_usize()]))
+            .collect();
+        if let Some(names) = &self.names {
+            found.extend(names.find(python_code));
+            found.sort_by_key(|(range, _)| (range.start, range.end));
+        }
+        found.into_iter().map(|(range, index)| (range, &self.rules[index]))
+    }
+
+    /// Returns the rule of the first threat found in `python_code`, if any.
+    pub fn find_threat(&self, python_code: &str) -> Option<&ThreatRule> {
+        match &self.names {
+            Some(_) => self.find_threats(python_code).next().map(|(_, rule)| rule),
+            None => self.detector.find(&Normalized::new(python_code).text)
+                .map(|m| &self.rules[self.substring_rules[m.pattern().as_usize()]]),
+        }
+    }
+
+    pub fn rules(&self) -> &[ThreatRule] {
+        &self.rules
+    }
+
+    pub fn import_policy(&self) -> Option<&ImportPolicy> {
+        self.imports.as_ref()
+    }
+
+    /// Imports in `python_code` the import policy refuses: the byte range of
+    /// the module name, the module and the rule it broke.
+    pub fn find_disallowed_imports(&self, python_code: &str) -> Vec<(Range<usize>, String, ThreatRule)> {
+        let Some(policy) = &self.imports else {
+            return Vec::new();
+        };
+        ast_detector::imports(python_code).into_iter()
+            .filter_map(|(module, range)| policy.check(&module).map(|rule| (range, module, rule)))
+            .collect()
+    }
+}
+
+// Code with comments, line continuations and whitespace dropped, except for a
+// single space where one is needed to keep two names apart. String literals
+// are kept verbatim. `offsets[i]` is where byte `i` of `text` came from.
+struct Normalized {
+    text: String,
+    offsets: Vec<usize>,
+}
+
+impl Normalized {
+    fn new(code: &str) -> Self {
+        let mut normalized = Self { text: String::with_capacity(code.len()), offsets: Vec::with_capacity(code.len()) };
+        let mut gap = None;
+        for segment in lexer::segments(code) {
+            let piece = &code[segment.range.clone()];
+            let literal = segment.kind == SegmentKind::Literal;
+            if literal && piece.starts_with('#') {
+                gap.get_or_insert(segment.range.start);
+                continue;
+            }
+            for (i, c) in piece.char_indices() {
+                let at = segment.range.start + i;
+                if !literal && (c.is_whitespace() || c == '\\') {
+                    gap.get_or_insert(at);
+                    continue;
+                }
+                if let Some(gap) = gap.take()
+                    && is_name_char(c)
+                    && normalized.text.chars().next_back().is_some_and(is_name_char)
+                {
+                    normalized.push(' ', gap);
+                }
+                normalized.push(c, at);
+            }
+        }
+        normalized
     }
-}
\ No newline at end of file
+
+    fn push(&mut self, c: char, at: usize) {
+        self.text.push(c);
+        self.offsets.extend((0..c.len_utf8()).map(|k| at + k));
+    }
+
+    // Maps a range of `text` back onto `code`.
+    fn original(&self, code: &str, range: Range<usize>) -> Range<usize> {
+        let start = self.offsets[range.start];
+        let mut end = self.offsets[range.end - 1] + 1;
+        while !code.is_char_boundary(end) {
+            end += 1;
+        }
+        start..end
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each threat as the original text it covers and its rule's pattern.
+    fn threats(code: &str) -> Vec<(String, String)> {
+        let detector = ThreatDetector::new().unwrap();
+        detector.find_threats(code).map(|(range, rule)| (code[range].to_string(), rule.pattern.clone())).collect()
+    }
+
+    fn threat(text: &str, pattern: &str) -> (String, String) {
+        (text.to_string(), pattern.to_string())
+    }
+
+    #[test]
+    fn whitespace_comments_and_continuations_are_seen_through() {
+        assert_eq!(threats("eval(1)"), [threat("eval(", "eval(")]);
+        assert_eq!(threats("x = eval  \t(1)"), [threat("eval  \t(", "eval(")]);
+        assert_eq!(threats("x = eval\\\n(1)"), [threat("eval\\\n(", "eval(")]);
+        assert_eq!(threats("x = eval\\\r\n(1)"), [threat("eval\\\r\n(", "eval(")]);
+        assert_eq!(threats("x = eval # c\n(1)"), [threat("eval # c\n(", "eval(")]);
+        assert_eq!(threats("os . system ('ls')"), [threat("os . system (", "os.system(")]);
+    }
+
+    #[test]
+    fn separated_names_stay_apart() {
+        assert!(threats("ev al(1)").is_empty());
+        assert!(threats("import os\nsystem = 1").is_empty());
+    }
+
+    #[test]
+    fn positions_map_back_to_the_original() {
+        let code = "λ = 1\ny = eval \\\n  ('1')";
+        let start = code.find("eval").unwrap();
+        let detector = ThreatDetector::new().unwrap();
+        let found: Vec<Range<usize>> = detector.find_threats(code).map(|(range, _)| range).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0], start..start + "eval \\\n  (".len());
+
+        let normalized = Normalized::new(code);
+        assert_eq!(normalized.text, "λ=1 y=eval('1')");
+        assert_eq!(normalized.offsets.len(), normalized.text.len());
+        assert_eq!(normalized.offsets[normalized.text.find("eval").unwrap()], start);
+    }
+
+    #[test]
+    fn literals_are_kept_verbatim() {
+        assert_eq!(Normalized::new("x = 'a  b' # c\ny").text, "x='a  b'y");
+        assert_eq!(Normalized::new("not  x").text, "not x");
+    }
+}
@@ -0,0 +1,569 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::error::{Error, Result, SecurityViolation, Stage};
+use crate::lexer::{self, Replacement, SegmentKind};
+use crate::mapping_file::MappingTable;
+use crate::matcher::{self, MatchEngine, Needle, SymbolMatcher};
+use crate::normalize::{Confusable, Confusables, Normalization};
+use crate::policy::{ImportAction, ThreatRule};
+use crate::reverse::{ReverseTable, RoundTripIssue};
+use crate::source_map::SourceMap;
+use crate::threat_detector::{DetectorBackend, ThreatDetector};
+use crate::validation::{self, IssueLevel, MappingIssue};
+use ahash::{AHashMap, AHashSet};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::ops::Range;
+
+/// What to do with threats found by scanning the whole transpiled output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputScan {
+    /// Only individual replacements are checked.
+    #[default]
+    Off,
+    /// Findings are kept for [`SymbolTranspiler::take_warnings`].
+    Warn,
+    /// Findings fail the transpilation with [`Error::Security`].
+    Block,
+}
+
+/// Rewrites PhiCode symbols into Python according to a mapping table.
+#[derive(Clone)]
+pub struct SymbolTranspiler {
+    mappings: AHashMap<String, String>,
+    engine: MatchEngine,
+    matcher: Option<SymbolMatcher>,
+    reverse: Option<ReverseTable>,
+    output_scan: OutputScan,
+    warnings: Vec<SecurityViolation>,
+    trusted: AHashSet<String>,
+    infix: AHashSet<String>,
+    exemptions: Vec<SecurityViolation>,
+    normalization: Normalization,
+    confusables: Confusables,
+    /// Matchable (composed) text of each configured symbol, to the symbol.
+    symbols: AHashMap<String, String>,
+    /// Matchable symbol texts the normalization form would fold.
+    kept: Vec<String>,
+    /// Matchable look-alikes, to the configured symbol they imitate.
+    lookalikes: AHashMap<String, String>,
+    found_confusables: Vec<Confusable>,
+}
+
+impl Default for SymbolTranspiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTranspiler {
+    /// Creates a transpiler with no mappings; it passes source through unchanged.
+    pub fn new() -> Self {
+        Self {
+            mappings: AHashMap::new(),
+            engine: MatchEngine::Regex,
+            matcher: None,
+            reverse: None,
+            output_scan: OutputScan::Off,
+            warnings: Vec::new(),
+            trusted: AHashSet::new(),
+            infix: AHashSet::new(),
+            exemptions: Vec::new(),
+            normalization: Normalization::None,
+            confusables: Confusables::default(),
+            symbols: AHashMap::new(),
+            kept: Vec::new(),
+            lookalikes: AHashMap::new(),
+            found_confusables: Vec::new(),
+        }
+    }
+
+    /// Selects how symbols are matched, recompiling configured mappings.
+    pub fn set_engine(&mut self, engine: MatchEngine) -> Result<()> {
+        self.engine = engine;
+        if let Some(matcher) = &self.matcher
+            && matcher.engine() != engine
+        {
+            self.compile()?;
+        }
+        Ok(())
+    }
+
+    pub fn engine(&self) -> MatchEngine {
+        self.engine
+    }
+
+    /// Selects the Unicode normalization applied to the code of sources before
+    /// matching, recompiling configured mappings. Symbols are only composed,
+    /// and kept unfolded in the source, so under NFKC `ℂ` still differs from
+    /// `C`. Positions in findings refer to the normalized source, see
+    /// [`normalize`](Self::normalize).
+    pub fn set_normalization(&mut self, normalization: Normalization) -> Result<()> {
+        self.normalization = normalization;
+        self.compile()
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// `source` as it is matched: its code in the configured normalization form.
+    pub fn normalize<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        self.normalization.apply_to_code(source, &self.kept)
+    }
+
+    /// Sets the look-alikes transpiled as the configured symbols they imitate,
+    /// recompiling configured mappings.
+    pub fn set_confusables(&mut self, confusables: Confusables) -> Result<()> {
+        self.confusables = confusables;
+        self.compile()
+    }
+
+    /// Look-alikes transpiled as symbols during the last transpilation.
+    pub fn take_confusables(&mut self) -> Vec<Confusable> {
+        std::mem::take(&mut self.found_confusables)
+    }
+
+    /// Sets how threats in the complete output (not just in replacements) are handled.
+    pub fn set_output_scan(&mut self, mode: OutputScan) {
+        self.output_scan = mode;
+    }
+
+    pub fn output_scan(&self) -> OutputScan {
+        self.output_scan
+    }
+
+    /// Findings from the last transpilation that only warn: output scan findings
+    /// in [`OutputScan::Warn`] mode and imports refused with [`ImportAction::Warn`].
+    pub fn take_warnings(&mut self) -> Vec<SecurityViolation> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Findings from the last transpilation that were allowed because they came
+    /// from a trusted symbol.
+    pub fn take_exemptions(&mut self) -> Vec<SecurityViolation> {
+        std::mem::take(&mut self.exemptions)
+    }
+
+    /// Configures the mappings of `table`, exempting its trusted symbols from
+    /// threat checks and matching its infix symbols anywhere.
+    pub fn configure_table(&mut self, table: MappingTable) -> Result<()> {
+        self.set_mappings(table.mappings, table.trusted, table.infix)
+    }
+
+    /// The configured symbols and their replacements.
+    pub fn mappings(&self) -> &AHashMap<String, String> {
+        &self.mappings
+    }
+
+    /// The configured symbols exempt from replacement threat checks.
+    pub fn trusted(&self) -> &AHashSet<String> {
+        &self.trusted
+    }
+
+    /// Replaces the mapping table and compiles the symbol matcher. No symbol
+    /// is trusted or infix. Empty or blank symbols are refused with
+    /// [`Error::Mappings`].
+    pub fn configure(&mut self, mappings: AHashMap<String, String>) -> Result<()> {
+        self.set_mappings(mappings, AHashSet::new(), AHashSet::new())
+    }
+
+    fn set_mappings(&mut self, mappings: AHashMap<String, String>, trusted: AHashSet<String>, infix: AHashSet<String>) -> Result<()> {
+        let errors: Vec<MappingIssue> = validation::validate_keys(&mappings).into_iter()
+            .filter(|issue| issue.level() == IssueLevel::Error)
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::Mappings(errors));
+        }
+
+        self.reverse = None;
+        self.mappings = mappings;
+        self.trusted = trusted;
+        self.infix = infix;
+        self.compile()
+    }
+
+    fn compile(&mut self) -> Result<()> {
+        self.symbols.clear();
+        self.kept.clear();
+        self.lookalikes.clear();
+        if self.mappings.is_empty() {
+            self.matcher = None;
+            return Ok(());
+        }
+
+        // Sorted so the first of several symbols normalizing alike always wins.
+        let mut symbols: Vec<&String> = self.mappings.keys().collect();
+        symbols.sort();
+        let needle = |text: &str, symbol: &String| if self.infix.contains(symbol) { Needle::anywhere(text) } else { Needle::symbol(text) };
+        let mut needles = Vec::new();
+        for symbol in symbols {
+            let text = self.normalization.apply_to_symbol(symbol).into_owned();
+            if !self.symbols.contains_key(&text) {
+                needles.push(needle(&text, symbol));
+                if self.normalization.apply(&text) != text.as_str() {
+                    self.kept.push(text.clone());
+                }
+                self.symbols.insert(text, symbol.clone());
+            }
+        }
+        // Longest first, so a kept symbol is never cut short by its prefix.
+        self.kept.sort_by_key(|text| std::cmp::Reverse(text.len()));
+        let mut confusables: Vec<(&String, &String)> = self.confusables.map.iter().collect();
+        confusables.sort();
+        for (found, imitated) in confusables {
+            if found.is_empty() || self.symbols.contains_key(found) || self.normalization.apply(found) != found.as_str() {
+                continue;
+            }
+            if let Some(symbol) = self.symbols.get(self.normalization.apply_to_symbol(imitated).as_ref()) {
+                // Never inside an identifier, whatever the imitated symbol.
+                needles.push(Needle::symbol(found));
+                self.lookalikes.insert(found.clone(), symbol.clone());
+            }
+        }
+        self.matcher = Some(SymbolMatcher::new(needles, self.engine)?);
+        if self.reverse.is_some() {
+            self.reverse = Some(ReverseTable::new(&self.mappings, self.engine)?);
+        }
+        Ok(())
+    }
+
+    /// Checks the configured mappings for overlapping symbols, replacements
+    /// containing symbols and, given a detector, replacements it would refuse.
+    pub fn validate(&self, threat_detector: Option<&ThreatDetector>) -> Vec<MappingIssue> {
+        validation::validate(&self.mappings, self.matcher.as_ref(), &self.trusted, &self.infix, threat_detector)
+    }
+
+    /// Configures `mappings` and additionally prepares the Python-to-symbol table.
+    pub fn configure_reverse(&mut self, mappings: AHashMap<String, String>) -> Result<()> {
+        self.configure(mappings)?;
+        self.reverse = Some(ReverseTable::new(&self.mappings, self.engine)?);
+        Ok(())
+    }
+
+    /// The reverse table, if [`configure_reverse`](Self::configure_reverse) was used.
+    pub fn reverse_table(&self) -> Option<&ReverseTable> {
+        self.reverse.as_ref()
+    }
+
+    /// Converts Python back into symbols, reporting spots that would not round-trip.
+    pub fn transpile_reverse(&self, source: &str) -> Result<(String, Vec<RoundTripIssue>)> {
+        let reverse = self.reverse.as_ref()
+            .ok_or(Error::NotConfigured("Reverse mode"))?;
+        Ok(reverse.transpile(source, self.matcher.as_ref(), &self.mappings))
+    }
+
+    /// Transpiles `source`, failing if any emitted replacement is dangerous
+    /// unless `bypass_security` is set.
+    pub fn transpile(&mut self, source: &str, threat_detector: &ThreatDetector, bypass_security: bool) -> Result<String> {
+        self.run(source, threat_detector, bypass_security, false)
+            .map(|(result, _)| result)
+    }
+
+    /// Like [`transpile`](Self::transpile), but keeps going after the first
+    /// dangerous replacement and still runs the import and output checks, so
+    /// the error lists every violation in the source. Output a refused
+    /// replacement produced is not reported a second time.
+    pub fn transpile_collecting(&mut self, source: &str, threat_detector: &ThreatDetector) -> Result<String> {
+        self.run(source, threat_detector, false, true)
+            .map(|(result, _)| result)
+    }
+
+    /// Like [`transpile`](Self::transpile), also returning a position map.
+    /// The map is relative to the [normalized](Self::normalize) source.
+    pub fn transpile_with_map(&mut self, source: &str, threat_detector: &ThreatDetector, bypass_security: bool) -> Result<(String, SourceMap)> {
+        let source = self.normalize(source);
+        let (result, replacements) = self.run(&source, threat_detector, bypass_security, false)?;
+        let map = SourceMap::new(&source, &result, &replacements);
+        Ok((result, map))
+    }
+
+    /// Transpiles `reader` into `writer` piece by piece, keeping memory bounded
+    /// by about `chunk_size` bytes plus the longest statement.
+    ///
+    /// Pieces end at line breaks between top-level statements, so symbols,
+    /// words and literals never straddle them. Findings are positioned in the
+    /// whole input, and warnings and exemptions cover every piece. On error,
+    /// the output of earlier pieces has already been written. Returns the
+    /// bytes read.
+    ///
+    /// Output scans and the AST backend need the whole output at once, since
+    /// code split across pieces or an alias bound in an earlier one would go
+    /// unseen; unless security is bypassed, both fail with
+    /// [`Error::Unsupported`] before anything is read.
+    pub fn transpile_stream<R: Read, W: Write>(
+        &mut self,
+        mut reader: R,
+        mut writer: W,
+        threat_detector: &ThreatDetector,
+        bypass_security: bool,
+        chunk_size: usize,
+    ) -> Result<usize> {
+        if !bypass_security && (self.output_scan != OutputScan::Off || threat_detector.backend() == DetectorBackend::Ast) {
+            return Err(Error::Unsupported("Streaming with output scans or the AST detector"));
+        }
+        let io_error = |e: std::io::Error| Error::Io(e.to_string());
+        let chunk_size = chunk_size.max(1);
+        let mut pending = Vec::new();
+        let mut wanted = chunk_size;
+        let mut consumed = 0;
+        let mut lines = 0;
+        let mut warnings = Vec::new();
+        let mut exemptions = Vec::new();
+        let mut confusables = Vec::new();
+        let mut done = false;
+
+        while !done {
+            let missing = wanted.saturating_sub(pending.len());
+            if missing > 0 {
+                let read = (&mut reader).take(missing as u64).read_to_end(&mut pending).map_err(io_error)?;
+                done = read < missing;
+            }
+            // A character split by the read waits for the next one.
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(e) if e.error_len().is_none() && !done => e.valid_up_to(),
+                Err(e) => return Err(Error::Io(format!("input is not valid UTF-8 at byte {}", consumed + e.valid_up_to()))),
+            };
+            let text = std::str::from_utf8(&pending[..valid]).map_err(|e| Error::Io(e.to_string()))?;
+            let cut = if done { Some(text.len()) } else { lexer::last_statement_break(text) };
+            let Some(cut) = cut else {
+                // Reading on until the buffer doubles keeps rescans linear.
+                wanted = pending.len() * 2;
+                continue;
+            };
+
+            let piece = &text[..cut];
+            let shift = |mut violations: Vec<SecurityViolation>| {
+                for violation in &mut violations {
+                    violation.offset += consumed;
+                    violation.line += lines;
+                }
+                violations
+            };
+            let (result, _) = self.run(piece, threat_detector, bypass_security, false).map_err(|e| match e {
+                Error::Security(violations) => Error::Security(shift(violations)),
+                other => other,
+            })?;
+            writer.write_all(result.as_bytes()).map_err(io_error)?;
+            warnings.extend(shift(self.take_warnings()));
+            exemptions.extend(shift(self.take_exemptions()));
+            confusables.extend(self.take_confusables().into_iter().map(|mut confusable| {
+                confusable.offset += consumed;
+                confusable.line += lines;
+                confusable
+            }));
+
+            lines += piece.matches('\n').count();
+            consumed += cut;
+            pending.drain(..cut);
+            wanted = chunk_size;
+        }
+        writer.flush().map_err(io_error)?;
+        self.warnings = warnings;
+        self.exemptions = exemptions;
+        self.found_confusables = confusables;
+        Ok(consumed)
+    }
+
+    fn run(&mut self, source: &str, threat_detector: &ThreatDetector, bypass_security: bool, collect: bool) -> Result<(String, Vec<Replacement>)> {
+        self.warnings.clear();
+        self.exemptions.clear();
+        self.found_confusables.clear();
+        let source = &*self.normalize(source);
+        let Replaced { output: result, replacements, exemptions, blocked: refused } =
+            self.transpile_replacements(source, threat_detector, bypass_security, collect)?;
+        self.exemptions = exemptions;
+        self.found_confusables = self.confusables_in(source, &replacements);
+        if bypass_security {
+            return Ok((result, replacements));
+        }
+
+        // Code a refused replacement produced is reported once, as that replacement.
+        let unrefused = |findings: Vec<SecurityViolation>| -> Vec<SecurityViolation> {
+            findings.into_iter()
+                .filter(|f| !refused.iter().any(|r| r.offset <= f.offset && f.offset + f.symbol.len() <= r.offset + r.symbol.len()))
+                .collect()
+        };
+        let mut blocked = refused.clone();
+        let mut warned = Vec::new();
+        if let Some(imports) = threat_detector.import_policy() {
+            let findings = unrefused(check_imports(source, &result, &replacements, threat_detector));
+            let findings = self.exempt_trusted(source, &replacements, findings);
+            match imports.action() {
+                ImportAction::Block => blocked.extend(findings),
+                ImportAction::Warn => warned.extend(findings),
+            }
+        }
+        if self.output_scan != OutputScan::Off {
+            let findings = unrefused(scan_output(source, &result, &replacements, threat_detector));
+            let findings = self.exempt_trusted(source, &replacements, findings);
+            match self.output_scan {
+                OutputScan::Block => blocked.extend(findings),
+                _ => warned.extend(findings),
+            }
+        }
+
+        self.exemptions.sort_by_key(|v| v.offset);
+        if !blocked.is_empty() {
+            blocked.sort_by_key(|v| v.offset);
+            return Err(Error::Security(blocked));
+        }
+        warned.sort_by_key(|v| v.offset);
+        self.warnings = warned;
+        Ok((result, replacements))
+    }
+
+    // Moves output findings lying wholly inside a trusted symbol's replacement
+    // to the exemptions, returning the rest.
+    fn exempt_trusted(&mut self, source: &str, replacements: &[Replacement], findings: Vec<SecurityViolation>) -> Vec<SecurityViolation> {
+        if self.trusted.is_empty() {
+            return findings;
+        }
+        let (exempt, rest): (Vec<_>, Vec<_>) = findings.into_iter().partition(|finding| {
+            replacements.iter().any(|r| r.source.start <= finding.offset
+                && finding.offset + finding.symbol.len() <= r.source.end
+                && self.symbol(&source[r.source.clone()]).is_some_and(|symbol| self.trusted.contains(symbol)))
+        });
+        self.exemptions.extend(exempt);
+        rest
+    }
+
+    fn confusables_in(&self, source: &str, replacements: &[Replacement]) -> Vec<Confusable> {
+        if self.lookalikes.is_empty() {
+            return Vec::new();
+        }
+        replacements.iter()
+            .filter_map(|r| {
+                let found = &source[r.source.clone()];
+                let symbol = self.lookalikes.get(found)?;
+                let (line, column) = lexer::line_col(source, r.source.start);
+                Some(Confusable { found: found.to_string(), symbol: symbol.clone(), offset: r.source.start, line, column })
+            })
+            .collect()
+    }
+
+    // The configured symbol a matched text stands for.
+    fn symbol(&self, matched: &str) -> Option<&String> {
+        self.symbols.get(matched).or_else(|| self.lookalikes.get(matched))
+    }
+
+    // Replaces the symbols of `source`. A dangerous replacement fails the call
+    // unless collecting, which keeps it in the output and returns it as blocked.
+    fn transpile_replacements(&self, source: &str, threat_detector: &ThreatDetector, bypass_security: bool, collect: bool) -> Result<Replaced> {
+        let matcher = match &self.matcher {
+            Some(m) if m.may_match(source) => m,
+            _ => return Ok(Replaced { output: source.to_string(), replacements: Vec::new(), exemptions: Vec::new(), blocked: Vec::new() }),
+        };
+
+        let mut blocked = Vec::new();
+        let mut exempted = Vec::new();
+        let mut index = 0;
+        let (result, replacements) = lexer::replace_in_code(matcher, source, |matched, range| {
+            index += 1;
+            if let Some(symbol) = self.symbol(matched)
+                && let Some(python_replacement) = self.mappings.get(symbol)
+            {
+                if !bypass_security
+                    && (collect || blocked.is_empty())
+                    && let Some(threat) = threat_detector.find_threat(python_replacement)
+                {
+                    let finding = (index - 1, matched.to_string(), python_replacement.clone(), threat.clone());
+                    if self.trusted.contains(symbol) {
+                        exempted.push(finding);
+                    } else {
+                        blocked.push(finding);
+                        if !collect {
+                            return "SECURITY_BLOCKED".to_string();
+                        }
+                    }
+                }
+                let keyword = !matcher::is_identifier(symbol) && matcher::is_keyword(python_replacement);
+                if keyword || self.infix.contains(symbol) {
+                    spaced(source, range, python_replacement)
+                } else {
+                    python_replacement.clone()
+                }
+            } else {
+                matched.to_string()
+            }
+        });
+
+        let violations = |findings: Vec<(usize, String, String, ThreatRule)>| -> Vec<SecurityViolation> {
+            findings.into_iter()
+                .map(|(index, symbol, replacement, rule)| {
+                    let offset = replacements[index].source.start;
+                    let (line, column) = lexer::line_col(source, offset);
+                    SecurityViolation::new(Stage::Replacement, symbol, replacement, &rule, offset, line, column)
+                })
+                .collect()
+        };
+        if !collect && !blocked.is_empty() {
+            return Err(Error::Security(violations(blocked)));
+        }
+
+        let exemptions = violations(exempted);
+        let blocked = violations(blocked);
+        Ok(Replaced { output: result, replacements, exemptions, blocked })
+    }
+}
+
+// What replacing the symbols of a source produced.
+struct Replaced {
+    output: String,
+    replacements: Vec<Replacement>,
+    exemptions: Vec<SecurityViolation>,
+    /// Dangerous replacements, only left in the output when collecting.
+    blocked: Vec<SecurityViolation>,
+}
+
+// Separates an infix or keyword symbol's replacement from identifier
+// characters it would merge with, so `a∈b` becomes `a in b` and `¬x` becomes
+// `not x`.
+fn spaced(source: &str, range: Range<usize>, replacement: &str) -> String {
+    let joins = |a: Option<char>, b: Option<char>| a.is_some_and(matcher::is_identifier_char) && b.is_some_and(matcher::is_identifier_char);
+    let before = joins(source[..range.start].chars().next_back(), replacement.chars().next());
+    let after = joins(replacement.chars().next_back(), source[range.end..].chars().next());
+    format!("{}{}{}", if before { " " } else { "" }, replacement, if after { " " } else { "" })
+}
+
+// Scans the output as a whole, so threats written directly in the source or
+// formed by adjacent replacements are found; matches starting inside string
+// literals are skipped.
+fn scan_output(source: &str, output: &str, replacements: &[Replacement], threat_detector: &ThreatDetector) -> Vec<SecurityViolation> {
+    let literals: Vec<Range<usize>> = lexer::segments(output).into_iter()
+        .filter(|segment| segment.kind == SegmentKind::Literal)
+        .map(|segment| segment.range)
+        .collect();
+    threat_detector.find_threats(output)
+        .filter(|(range, _)| {
+            let inside = literals.partition_point(|l| l.end <= range.start);
+            literals.get(inside).is_none_or(|l| l.start > range.start)
+        })
+        .map(|(range, rule)| {
+            let matched = output[range.clone()].to_string();
+            output_violation(Stage::Output, source, replacements, range, matched, rule)
+        })
+        .collect()
+}
+
+fn check_imports(source: &str, output: &str, replacements: &[Replacement], threat_detector: &ThreatDetector) -> Vec<SecurityViolation> {
+    threat_detector.find_disallowed_imports(output).into_iter()
+        .map(|(range, module, rule)| output_violation(Stage::Import, source, replacements, range, module, &rule))
+        .collect()
+}
+
+// Builds a finding for the output bytes `range`, located at the source text
+// they were generated from.
+fn output_violation(stage: Stage, source: &str, replacements: &[Replacement], range: Range<usize>, replacement: String, rule: &ThreatRule) -> SecurityViolation {
+    let start = lexer::source_offset(replacements, range.start);
+    let last = range.end - 1;
+    let index = replacements.partition_point(|r| r.output.start <= last);
+    let end = match index.checked_sub(1).map(|i| &replacements[i]) {
+        Some(r) if last < r.output.end => r.source.end,
+        _ => lexer::source_offset(replacements, last) + 1,
+    };
+    let (line, column) = lexer::line_col(source, start);
+    SecurityViolation::new(stage, source[start..end].to_string(), replacement, rule, start, line, column)
+}
@@ -0,0 +1,207 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::matcher::{self, SymbolMatcher};
+use crate::threat_detector::ThreatDetector;
+use ahash::{AHashMap, AHashSet};
+use std::fmt;
+
+/// Whether a [`MappingIssue`] stops the mappings from being configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueLevel {
+    Warning,
+    Error,
+}
+
+/// What is wrong with a mapping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The symbol is the empty string and would match everywhere.
+    EmptyKey,
+    /// The symbol is only whitespace.
+    BlankKey,
+    /// The symbol starts or ends with whitespace, which is easy to miss.
+    SurroundingWhitespace,
+    /// The symbol also occurs inside `longer`, which wins where both match.
+    Overlaps { longer: String },
+    /// The replacement contains these symbols, which are not expanded again.
+    ContainsSymbols { symbols: Vec<String> },
+    /// The replacement is refused by the threat detector whenever it is used.
+    Dangerous { pattern: String, category: String },
+}
+
+/// A problem found in one mapping by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingIssue {
+    pub symbol: String,
+    pub kind: IssueKind,
+}
+
+impl MappingIssue {
+    pub fn level(&self) -> IssueLevel {
+        match self.kind {
+            IssueKind::EmptyKey | IssueKind::BlankKey => IssueLevel::Error,
+            _ => IssueLevel::Warning,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match &self.kind {
+            IssueKind::EmptyKey => "empty symbol".to_string(),
+            IssueKind::BlankKey => format!("symbol {:?} is only whitespace", self.symbol),
+            IssueKind::SurroundingWhitespace => format!("symbol {:?} has leading or trailing whitespace", self.symbol),
+            IssueKind::Overlaps { longer } => format!("`{}` is part of `{}`, which takes precedence", self.symbol, longer),
+            IssueKind::ContainsSymbols { symbols } => format!(
+                "replacement for `{}` contains the symbol(s) `{}`, which are not transpiled again",
+                self.symbol, symbols.join("`, `"),
+            ),
+            IssueKind::Dangerous { pattern, category } => format!(
+                "replacement for `{}` matches {} threat pattern `{}` and is blocked wherever it is used",
+                self.symbol, category, pattern,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for MappingIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message())
+    }
+}
+
+/// Checks the symbols of `mappings` on their own; these are the checks that
+/// can fail configuration.
+pub fn validate_keys(mappings: &AHashMap<String, String>) -> Vec<MappingIssue> {
+    let mut issues = Vec::new();
+    for symbol in mappings.keys() {
+        let kind = if symbol.is_empty() {
+            IssueKind::EmptyKey
+        } else if symbol.trim().is_empty() {
+            IssueKind::BlankKey
+        } else if symbol.trim() != symbol {
+            IssueKind::SurroundingWhitespace
+        } else {
+            continue;
+        };
+        issues.push(MappingIssue { symbol: symbol.clone(), kind });
+    }
+    sort(&mut issues);
+    issues
+}
+
+/// Runs every check over configured mappings. `matcher` finds the configured
+/// symbols; trusted symbols are not checked against `threat_detector`, and
+/// infix symbols match anywhere.
+pub fn validate(
+    mappings: &AHashMap<String, String>,
+    matcher: Option<&SymbolMatcher>,
+    trusted: &AHashSet<String>,
+    infix: &AHashSet<String>,
+    threat_detector: Option<&ThreatDetector>,
+) -> Vec<MappingIssue> {
+    let mut issues = validate_keys(mappings);
+    let whole_token = |symbol: &str| !infix.contains(symbol) && matcher::is_identifier(symbol);
+
+    for symbol in mappings.keys().filter(|s| !s.is_empty()) {
+        // Whole-token symbols never match inside one another.
+        let longer = mappings.keys()
+            .filter(|other| other.len() > symbol.len() && other.contains(symbol.as_str()))
+            .filter(|other| !(whole_token(symbol) && whole_token(other)))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        if let Some(longer) = longer {
+            issues.push(MappingIssue { symbol: symbol.clone(), kind: IssueKind::Overlaps { longer: longer.clone() } });
+        }
+    }
+
+    for (symbol, replacement) in mappings {
+        if let Some(matcher) = matcher {
+            let mut symbols: Vec<String> = matcher.find_iter(replacement).map(|m| replacement[m].to_string()).collect();
+            symbols.sort();
+            symbols.dedup();
+            if !symbols.is_empty() {
+                issues.push(MappingIssue { symbol: symbol.clone(), kind: IssueKind::ContainsSymbols { symbols } });
+            }
+        }
+        if let Some(detector) = threat_detector
+            && !trusted.contains(symbol)
+            && let Some(rule) = detector.find_threat(replacement)
+        {
+            issues.push(MappingIssue {
+                symbol: symbol.clone(),
+                kind: IssueKind::Dangerous { pattern: rule.pattern.clone(), category: rule.category.clone() },
+            });
+        }
+    }
+
+    sort(&mut issues);
+    issues
+}
+
+fn sort(issues: &mut [MappingIssue]) {
+    issues.sort_by(|a, b| b.level().cmp(&a.level()).then_with(|| a.symbol.cmp(&b.symbol)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{MatchEngine, Needle};
+
+    fn table(pairs: &[(&str, &str)]) -> AHashMap<String, String> {
+        pairs.iter().map(|(symbol, python)| (symbol.to_string(), python.to_string())).collect()
+    }
+
+    fn issues(pairs: &[(&str, &str)], trusted: &[&str], infix: &[&str], detector: Option<&ThreatDetector>) -> Vec<MappingIssue> {
+        let mappings = table(pairs);
+        let infix: AHashSet<String> = infix.iter().map(|s| s.to_string()).collect();
+        let trusted: AHashSet<String> = trusted.iter().map(|s| s.to_string()).collect();
+        let needles = mappings.keys()
+            .map(|s| if infix.contains(s) { Needle::anywhere(s) } else { Needle::symbol(s) })
+            .collect();
+        let matcher = SymbolMatcher::new(needles, MatchEngine::Regex).unwrap();
+        validate(&mappings, Some(&matcher), &trusted, &infix, detector)
+    }
+
+    fn issue(symbol: &str, kind: IssueKind) -> MappingIssue {
+        MappingIssue { symbol: symbol.to_string(), kind }
+    }
+
+    #[test]
+    fn bad_keys_are_errors_first() {
+        let found = validate_keys(&table(&[("", "a"), ("  ", "b"), (" ƒ", "def"), ("λ", "lambda")]));
+        assert_eq!(found, [
+            issue("", IssueKind::EmptyKey),
+            issue("  ", IssueKind::BlankKey),
+            issue(" ƒ", IssueKind::SurroundingWhitespace),
+        ]);
+        assert_eq!(found.iter().map(MappingIssue::level).collect::<Vec<_>>(), [IssueLevel::Error, IssueLevel::Error, IssueLevel::Warning]);
+    }
+
+    #[test]
+    fn overlapping_symbols_name_the_shortest_longer_one() {
+        assert_eq!(issues(&[("→", "return"), ("→→", "yield"), ("→→→", "yield from")], &[], &[], None), [
+            issue("→", IssueKind::Overlaps { longer: "→→".to_string() }),
+            issue("→→", IssueKind::Overlaps { longer: "→→→".to_string() }),
+        ]);
+        // Whole-token symbols never match inside one another.
+        assert_eq!(issues(&[("fn", "def"), ("fnx", "lambda")], &[], &[], None), []);
+        // Unless one of them is infix.
+        assert_eq!(issues(&[("in", "IN"), ("fin", "FIN")], &[], &["in"], None), [
+            issue("in", IssueKind::Overlaps { longer: "fin".to_string() }),
+        ]);
+    }
+
+    #[test]
+    fn replacements_containing_symbols_are_reported() {
+        assert_eq!(issues(&[("ƒ", "def ƒ"), ("λ", "lambda")], &[], &[], None), [
+            issue("ƒ", IssueKind::ContainsSymbols { symbols: vec!["ƒ".to_string()] }),
+        ]);
+    }
+
+    #[test]
+    fn dangerous_replacements_are_reported_unless_trusted() {
+        let detector = ThreatDetector::new().unwrap();
+        let dangerous = IssueKind::Dangerous { pattern: "eval(".to_string(), category: "code-execution".to_string() };
+        assert_eq!(issues(&[("ε", "eval("), ("⊢", "exec(")], &["⊢"], &[], Some(&detector)), [issue("ε", dangerous)]);
+        assert_eq!(issues(&[("ε", "eval(")], &[], &[], None), []);
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use crate::batch;
+use crate::error::{Error, Result};
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// How a [`SourceWatcher`] learns about changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchBackend {
+    /// Filesystem notifications from the operating system.
+    Native,
+    /// Periodic scans, used when notifications are unavailable or requested.
+    Polling(Duration),
+}
+
+impl fmt::Display for WatchBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchBackend::Native => f.write_str("filesystem notifications"),
+            WatchBackend::Polling(interval) => write!(f, "polling every {:?}", interval),
+        }
+    }
+}
+
+/// Watches the files, directories and glob roots of batch inputs and reports
+/// changed paths in debounced groups.
+pub struct SourceWatcher {
+    _watcher: Box<dyn Watcher + Send>,
+    events: Receiver<PathBuf>,
+    backend: WatchBackend,
+}
+
+impl SourceWatcher {
+    /// Starts watching `inputs` with notifications, falling back to polling
+    /// every `poll_interval` if they cannot be set up or `poll` is set.
+    pub fn new(inputs: &[String], poll_interval: Duration, poll: bool) -> Result<Self> {
+        let roots: Vec<PathBuf> = inputs.iter().map(|input| watch_root(input)).collect();
+        let (sender, events) = mpsc::channel();
+
+        if !poll
+            && let Ok(watcher) = start(RecommendedWatcher::new(forward(sender.clone()), Config::default()), &roots)
+        {
+            return Ok(Self { _watcher: Box::new(watcher), events, backend: WatchBackend::Native });
+        }
+        let config = Config::default().with_poll_interval(poll_interval);
+        let watcher = start(PollWatcher::new(forward(sender), config), &roots)?;
+        Ok(Self { _watcher: Box::new(watcher), events, backend: WatchBackend::Polling(poll_interval) })
+    }
+
+    pub fn backend(&self) -> WatchBackend {
+        self.backend
+    }
+
+    /// Blocks for the next change, then keeps collecting until `debounce`
+    /// passes without one. Returns the changed paths without duplicates, or
+    /// `None` once the watcher has stopped.
+    pub fn next_changes(&self, debounce: Duration) -> Option<Vec<PathBuf>> {
+        let mut changed = vec![self.events.recv().ok()?];
+        loop {
+            match self.events.recv_timeout(debounce) {
+                Ok(path) => changed.push(path),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        Some(changed)
+    }
+}
+
+fn forward(sender: Sender<PathBuf>) -> impl Fn(notify::Result<Event>) + Send + 'static {
+    move |event| {
+        if let Ok(event) = event
+            && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        {
+            for path in event.paths {
+                let _ = sender.send(path);
+            }
+        }
+    }
+}
+
+fn start<W: Watcher>(watcher: notify::Result<W>, roots: &[PathBuf]) -> Result<W> {
+    let failed = |e: notify::Error| Error::Watch(e.to_string());
+    let mut watcher = watcher.map_err(failed)?;
+    for root in roots {
+        let mode = if root.is_dir() { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(root, mode).map_err(failed)?;
+    }
+    Ok(watcher)
+}
+
+// The path to watch for an input: the file or directory itself, or the part
+// of a glob pattern before its first wildcard.
+fn watch_root(input: &str) -> PathBuf {
+    let path = Path::new(input);
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    let root = batch::glob_root(input);
+    if root.as_os_str().is_empty() { PathBuf::from(".") } else { root }
+}
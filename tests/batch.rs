@@ -0,0 +1,70 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::batch::{self, BatchOptions, FileStatus, Summary};
+use phirust_transpiler::mapping_file::MappingTable;
+use phirust_transpiler::{MatchEngine, OutputScan, ThreatDetector};
+use std::path::PathBuf;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("phirust-batch-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src/pkg")).unwrap();
+    std::fs::write(dir.join("src/main.φ"), "ƒ main(): pass\n").unwrap();
+    std::fs::write(dir.join("src/pkg/util.φ"), "ƒ util(): ⟲ eval('1')\n").unwrap();
+    std::fs::write(dir.join("src/notes.txt"), "ƒ\n").unwrap();
+    dir
+}
+
+fn table() -> MappingTable {
+    let mappings = [("ƒ", "def"), ("⟲", "return")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    MappingTable { mappings, ..Default::default() }
+}
+
+#[test]
+fn batches_mirror_the_tree_on_every_engine() {
+    for engine in [MatchEngine::Regex, MatchEngine::AhoCorasick] {
+        let dir = scratch(&format!("{:?}", engine));
+        let files = batch::collect_inputs(&[dir.join("src").display().to_string()], &["φ".to_string()]).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let options = BatchOptions { out_dir: Some(dir.join("out")), engine, jobs: 2, output_scan: OutputScan::Block, ..Default::default() };
+        let results = batch::run(&files, &table(), &ThreatDetector::new().unwrap(), &options).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("out/main.py")).unwrap(), "def main(): pass\n");
+        assert!(matches!(results[1].status, FileStatus::Blocked(_)));
+        let summary = Summary::new(&results);
+        assert_eq!((summary.transpiled, summary.blocked), (1, 1));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn bad_tables_fail_the_run() {
+    let dir = scratch("bad");
+    let files = batch::collect_inputs(&[dir.join("src/main.φ").display().to_string()], &[]).unwrap();
+    let mut table = table();
+    table.mappings.insert(" ".to_string(), "x".to_string());
+    assert!(batch::run(&files, &table, &ThreatDetector::new().unwrap(), &BatchOptions::default()).is_err());
+    assert!(!dir.join("src/main.py").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn colliding_outputs_fail_without_writing() {
+    let dir = scratch("collide");
+    std::fs::create_dir_all(dir.join("other")).unwrap();
+    std::fs::write(dir.join("other/main.φ"), "ƒ other(): pass\n").unwrap();
+    let inputs = [dir.join("src").display().to_string(), dir.join("other").display().to_string()];
+    let files = batch::collect_inputs(&inputs, &["φ".to_string()]).unwrap();
+    assert_eq!(files.len(), 3);
+
+    let options = BatchOptions { out_dir: Some(dir.join("out")), jobs: 2, ..Default::default() };
+    let results = batch::run(&files, &table(), &ThreatDetector::new().unwrap(), &options).unwrap();
+    let failed: Vec<_> = results.iter().filter(|r| matches!(r.status, FileStatus::Failed(_))).collect();
+    assert_eq!(failed.len(), 2);
+    assert!(failed.iter().all(|r| r.output == dir.join("out/main.py")));
+    assert!(matches!(&failed[0].status, FileStatus::Failed(message) if message.contains("other")));
+    assert!(!dir.join("out/main.py").exists());
+    assert!(dir.join("out/pkg/util.py").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,63 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::cache::{self, Cache, CacheStats};
+use phirust_transpiler::mapping_file::MappingTable;
+use phirust_transpiler::{Confusables, Normalization, OutputScan, ThreatDetector};
+use std::path::PathBuf;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("phirust-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn fingerprint(table: &MappingTable) -> String {
+    let detector = ThreatDetector::new().unwrap();
+    cache::fingerprint(table, Normalization::None, &Confusables::default(), &detector, OutputScan::Off, false)
+}
+
+fn table(replacement: &str) -> MappingTable {
+    MappingTable { mappings: [("ƒ".to_string(), replacement.to_string())].into_iter().collect(), ..Default::default() }
+}
+
+#[test]
+fn stored_outputs_are_hits() {
+    let dir = scratch("hit");
+    let cache = Cache::open(&dir, fingerprint(&table("def"))).unwrap();
+    assert_eq!(cache.get("ƒ f(): pass"), None);
+    cache.put("ƒ f(): pass", "def f(): pass");
+    assert_eq!(cache.get("ƒ f(): pass").as_deref(), Some("def f(): pass"));
+    assert_eq!(cache.get("ƒ g(): pass"), None);
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+    assert_eq!(cache.stats().hit_rate(), 1.0 / 3.0);
+
+    // Entries outlive the cache that wrote them.
+    let reopened = Cache::open(&dir, fingerprint(&table("def"))).unwrap();
+    assert_eq!(reopened.get("ƒ f(): pass").as_deref(), Some("def f(): pass"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn configuration_changes_are_misses() {
+    let dir = scratch("miss");
+    let original = fingerprint(&table("def"));
+    Cache::open(&dir, original.clone()).unwrap().put("ƒ f(): pass", "def f(): pass");
+
+    let mut trusted = table("def");
+    trusted.trusted.insert("ƒ".to_string());
+    let detector = ThreatDetector::new().unwrap();
+    let changed = [
+        fingerprint(&table("async def")),
+        fingerprint(&trusted),
+        cache::fingerprint(&table("def"), Normalization::Nfc, &Confusables::default(), &detector, OutputScan::Off, false),
+        cache::fingerprint(&table("def"), Normalization::None, &Confusables::builtin(), &detector, OutputScan::Off, false),
+        cache::fingerprint(&table("def"), Normalization::None, &Confusables::default(), &detector, OutputScan::Warn, false),
+        cache::fingerprint(&table("def"), Normalization::None, &Confusables::default(), &detector, OutputScan::Off, true),
+    ];
+    for fingerprint in changed {
+        assert_ne!(fingerprint, original);
+        assert_eq!(Cache::open(&dir, fingerprint).unwrap().get("ƒ f(): pass"), None);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,76 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    run_pieces(args, &[stdin])
+}
+
+// Writes stdin in separately flushed pieces, pausing between them.
+fn run_pieces(args: &[&str], pieces: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_phirust-transpiler"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > 0 {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        stdin.write_all(piece.as_bytes()).unwrap();
+        stdin.flush().unwrap();
+    }
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn blocked_replacements_print_a_diagnostic() {
+    let output = run(&["--symbols", r#"{"∂":"eval("}"#, "--source-name", "a.φ"], "x = 1\n𝑓 = ∂1) ∂2)\n");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(stderr(&output).ends_with("\
+error: dangerous replacement for symbol `∂`
+ --> a.φ:2:5
+  |
+2 | 𝑓 = ∂1) ∂2)
+  |     ^ `∂` expands to `eval(`, matching code-execution threat pattern `eval(`
+"), "{}", stderr(&output));
+}
+
+#[test]
+fn output_warnings_point_past_multi_byte_characters() {
+    let output = run(&["--symbols", r#"{"ƒ":"def"}"#, "--scan-output", "warn", "--source-name", "a.φ"], "ƒ 𝑓(): ⟨eval(1)\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout.clone()).unwrap(), "def 𝑓(): ⟨eval(1)\n");
+    assert_eq!(stderr(&output), "\
+warning: dangerous code in transpiled output
+ --> a.φ:1:9
+  |
+1 | ƒ 𝑓(): ⟨eval(1)
+  |         ^^^^^ transpiled code `eval(` matches code-execution threat pattern `eval(`
+
+");
+}
+
+#[test]
+fn streamed_headers_may_arrive_in_pieces() {
+    let args = ["--dialect", "phicode", "--stream", "--strict-dialect"];
+    let output = run_pieces(&args, &["#!/usr/bin/env python\n# dia", "lect: phicode 0.", "9.0\nƒ f(): pass\n"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("<stdin>:2: source targets phicode 0.9.0"), "{}", stderr(&output));
+
+    let output = run_pieces(&args, &["# dialect: phicode 1.1.0\n", "ƒ f(): pass\n"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "# dialect: phicode 1.1.0\ndef f(): pass\n");
+}
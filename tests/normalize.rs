@@ -0,0 +1,104 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::dialect;
+use phirust_transpiler::mapping_file::MappingTable;
+use phirust_transpiler::{Confusables, Normalization, SymbolTranspiler, ThreatDetector};
+
+fn transpiler(mappings: &[(&str, &str)], normalization: Normalization, confusables: Confusables) -> SymbolTranspiler {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.set_normalization(normalization).unwrap();
+    transpiler.set_confusables(confusables).unwrap();
+    transpiler.configure(mappings.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()).unwrap();
+    transpiler
+}
+
+#[test]
+fn decomposed_symbols_match_under_nfc() {
+    let detector = ThreatDetector::new().unwrap();
+    let source = "cafe\u{301} = \"cafe\u{301}\"\n";
+
+    let mut plain = transpiler(&[("caf\u{e9}", "coffee")], Normalization::None, Confusables::default());
+    assert_eq!(plain.transpile(source, &detector, false).unwrap(), source);
+
+    let mut nfc = transpiler(&[("caf\u{e9}", "coffee")], Normalization::Nfc, Confusables::default());
+    assert_eq!(nfc.transpile(source, &detector, false).unwrap(), "coffee = \"cafe\u{301}\"\n");
+}
+
+#[test]
+fn literal_delimiters_are_never_normalized() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = transpiler(&[("y", "why")], Normalization::Nfkc, Confusables::default());
+    let source = "\u{ff58} = \u{ff02}y\u{ff02}\n\u{ff59} = 1\n";
+    assert_eq!(transpiler.transpile(source, &detector, false).unwrap(), "\u{ff58} = \u{ff02}why\u{ff02}\nwhy = 1\n");
+}
+
+#[test]
+fn confusables_are_transpiled_and_reported() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.set_normalization(Normalization::Nfc).unwrap();
+    transpiler.set_confusables(Confusables::builtin()).unwrap();
+    transpiler.configure_table(MappingTable {
+        mappings: [("ƒ", "def"), ("∈", "in")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        infix: ["∈".to_string()].into_iter().collect(),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(transpiler.transpile("ƒ a(): pass
+x = 1∊s
+", &detector, false).unwrap(), "def a(): pass
+x = 1 in s
+");
+
+    let found = transpiler.take_confusables();
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].found.as_str(), found[0].symbol.as_str(), found[0].line, found[0].column), ("∊", "∈", 2, 6));
+}
+
+#[test]
+fn identifier_confusables_stay_identifiers() {
+    let detector = ThreatDetector::new().unwrap();
+    let source = "ϵ = 1e-9
+xϵs = 2
+";
+    let mut phicode = SymbolTranspiler::new();
+    phicode.set_confusables(Confusables::builtin()).unwrap();
+    phicode.configure_table(dialect::resolve("phicode").unwrap().table).unwrap();
+    assert_eq!(phicode.transpile(source, &detector, false).unwrap(), source);
+    assert!(phicode.take_confusables().is_empty());
+
+    // A table may still map a letter, but only whole identifiers match.
+    let epsilon = Confusables { map: [("ϵ".to_string(), "∈".to_string())].into_iter().collect() };
+    phicode.set_confusables(epsilon).unwrap();
+    assert_eq!(phicode.transpile("xϵs = x ϵ s
+", &detector, false).unwrap(), "xϵs = x in s
+");
+}
+
+#[test]
+fn nfkc_never_folds_symbols() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut phicode = SymbolTranspiler::new();
+    phicode.set_normalization(Normalization::Nfkc).unwrap();
+    phicode.configure_table(dialect::resolve("phicode").unwrap().table).unwrap();
+
+    // `ℂ` and `‼` fold to `C` and `!!`, which must neither match nor be lost.
+    let source = "π(C)\nℂ Ａ: ⋯\n‼ ｘ\n";
+    assert_eq!(phicode.transpile(source, &detector, false).unwrap(), "print(C)\nclass A: pass\nassert x\n");
+    assert_eq!(phicode.normalize(source), "π(C)\nℂ A: ⋯\n‼ x\n");
+}
+
+#[test]
+fn styled_letters_need_a_table() {
+    let detector = ThreatDetector::new().unwrap();
+    let source = "𝑓 main(): pass\n𝑓x = 1\n";
+    let mut phicode = SymbolTranspiler::new();
+    phicode.set_confusables(Confusables::builtin()).unwrap();
+    phicode.configure_table(dialect::resolve("phicode").unwrap().table).unwrap();
+    assert_eq!(phicode.transpile(source, &detector, false).unwrap(), source);
+
+    let styled = Confusables { map: [("𝑓".to_string(), "ƒ".to_string())].into_iter().collect() };
+    phicode.set_confusables(styled).unwrap();
+    assert_eq!(phicode.transpile(source, &detector, false).unwrap(), "def main(): pass\n𝑓x = 1\n");
+    assert_eq!(phicode.take_confusables().len(), 1);
+}
@@ -0,0 +1,85 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use ahash::AHashMap;
+use phirust_transpiler::{dialect, lexer};
+use phirust_transpiler::matcher::{Needle, SymbolMatcher};
+use phirust_transpiler::{MatchEngine, SymbolTranspiler, ThreatDetector};
+use proptest::prelude::*;
+
+// ASCII, Unicode and mixed characters, including word and punctuation ones so
+// symbols meet boundaries on either side.
+const SYMBOL_CHARS: &[&str] = &["f", "n", "a", "_", "1", "-", ">", "λ", "ƒ", "→", "é", "∀"];
+const SOURCE_CHARS: &[&str] = &[" ", "\n", "(", ")", ":", "\"", "'", "#", "x"];
+
+fn symbol() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(SYMBOL_CHARS), 1..4).prop_map(|chars| chars.concat())
+}
+
+fn mappings() -> impl Strategy<Value = AHashMap<String, String>> {
+    prop::collection::btree_set(symbol(), 1..8).prop_map(|symbols| {
+        symbols.into_iter()
+            .enumerate()
+            .map(|(i, symbol)| (symbol, format!("r{}", i)))
+            .collect()
+    })
+}
+
+fn source() -> impl Strategy<Value = String> {
+    let chars: Vec<&str> = SYMBOL_CHARS.iter().chain(SOURCE_CHARS).copied().collect();
+    prop::collection::vec(prop::sample::select(chars), 0..48).prop_map(|chars| chars.concat())
+}
+
+fn engine() -> impl Strategy<Value = MatchEngine> {
+    prop::sample::select(vec![MatchEngine::Regex, MatchEngine::AhoCorasick])
+}
+
+fn matcher(mappings: &AHashMap<String, String>, engine: MatchEngine) -> SymbolMatcher {
+    let needles = mappings.keys().map(|symbol| Needle::symbol(symbol)).collect();
+    SymbolMatcher::new(needles, engine).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn prefilter_never_hides_a_match(mappings in mappings(), source in source(), engine in engine()) {
+        let matcher = matcher(&mappings, engine);
+        if !matcher.may_match(&source) {
+            prop_assert_eq!(matcher.find_iter(&source).next(), None);
+        }
+    }
+
+    #[test]
+    fn transpile_equals_unfiltered_replacement(mappings in mappings(), source in source(), engine in engine()) {
+        let expected = lexer::replace_in_code(&matcher(&mappings, engine), &source, |symbol, _| mappings[symbol].clone()).0;
+
+        let mut transpiler = SymbolTranspiler::new();
+        transpiler.set_engine(engine).unwrap();
+        transpiler.configure(mappings).unwrap();
+        let detector = ThreatDetector::new().unwrap();
+        prop_assert_eq!(transpiler.transpile(&source, &detector, true).unwrap(), expected);
+    }
+
+    #[test]
+    fn engines_agree(mappings in mappings(), source in source()) {
+        let regex = matcher(&mappings, MatchEngine::Regex);
+        let automaton = matcher(&mappings, MatchEngine::AhoCorasick);
+        prop_assert_eq!(regex.find_iter(&source).collect::<Vec<_>>(), automaton.find_iter(&source).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn ascii_symbols_are_applied() {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.configure([("fn".to_string(), "def".to_string())].into_iter().collect()).unwrap();
+    let detector = ThreatDetector::new().unwrap();
+    assert_eq!(transpiler.transpile("fn f(): pass\n", &detector, false).unwrap(), "def f(): pass\n");
+}
+
+#[test]
+fn keyword_symbols_stay_separate_tokens() {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.configure_table(dialect::resolve("phicode").unwrap().table).unwrap();
+    let detector = ThreatDetector::new().unwrap();
+    assert_eq!(transpiler.transpile("¿ ¬x: ⟲x\n", &detector, false).unwrap(), "if not x: return x\n");
+    assert_eq!(transpiler.transpile("⟲(¬x)\n", &detector, false).unwrap(), "return(not x)\n");
+}
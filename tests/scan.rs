@@ -0,0 +1,93 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::mapping_file::MappingTable;
+use phirust_transpiler::{DetectorBackend, Error, ImportPolicy, OutputScan, Stage, SymbolTranspiler, ThreatDetector, ThreatPolicy};
+
+// `eval(` is written in plain Python, so only the output scan can see it.
+const SOURCE: &str = "ƒ f(x):\n    ⟲ eval(x)\n";
+
+fn transpiler(scan: OutputScan) -> SymbolTranspiler {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.configure([("ƒ", "def"), ("⟲", "return")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()).unwrap();
+    transpiler.set_output_scan(scan);
+    transpiler
+}
+
+#[test]
+fn scans_are_off_by_default() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = transpiler(OutputScan::Off);
+    assert_eq!(transpiler.transpile(SOURCE, &detector, false).unwrap(), "def f(x):\n    return eval(x)\n");
+    assert!(transpiler.take_warnings().is_empty());
+}
+
+#[test]
+fn warning_scans_keep_the_output() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = transpiler(OutputScan::Warn);
+    assert_eq!(transpiler.transpile(SOURCE, &detector, false).unwrap(), "def f(x):\n    return eval(x)\n");
+    let warnings = transpiler.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!((warnings[0].stage, warnings[0].symbol.as_str(), warnings[0].line, warnings[0].column), (Stage::Output, "eval(", 2, 7));
+}
+
+#[test]
+fn blocking_scans_refuse_the_output() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = transpiler(OutputScan::Block);
+    let Err(Error::Security(violations)) = transpiler.transpile(SOURCE, &detector, false) else {
+        panic!("dangerous output allowed");
+    };
+    assert_eq!(violations.len(), 1);
+    assert_eq!((violations[0].stage, violations[0].offset), (Stage::Output, SOURCE.find("eval").unwrap()));
+
+    // Bypassing skips the scan.
+    assert_eq!(transpiler.transpile(SOURCE, &detector, true).unwrap(), "def f(x):\n    return eval(x)\n");
+}
+
+#[test]
+fn collecting_reports_every_kind_of_finding() {
+    let policy = ThreatPolicy::builtin().with_imports(ImportPolicy::deny(vec!["os".to_string()]));
+    let detector = ThreatDetector::with_backend(&policy, DetectorBackend::Substring).unwrap();
+    let mut transpiler = transpiler(OutputScan::Block);
+    transpiler.configure([("ƒ", "def"), ("⟲", "return"), ("∂", "exec(")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()).unwrap();
+    let source = "import os\n∂'1')\nƒ f(x):\n    ⟲ eval(x)\n";
+
+    let Err(Error::Security(violations)) = transpiler.transpile_collecting(source, &detector) else {
+        panic!("dangerous source allowed");
+    };
+    let found: Vec<(Stage, usize)> = violations.iter().map(|v| (v.stage, v.line)).collect();
+    // The `exec(` from `∂` is only reported as a replacement.
+    assert_eq!(found, [(Stage::Import, 1), (Stage::Replacement, 2), (Stage::Output, 4)]);
+
+    // Without collecting, the first dangerous replacement stops the checks.
+    let Err(Error::Security(violations)) = transpiler.transpile(source, &detector, false) else {
+        panic!("dangerous source allowed");
+    };
+    assert_eq!(violations.iter().map(|v| v.stage).collect::<Vec<_>>(), [Stage::Replacement]);
+}
+
+#[test]
+fn trusted_symbols_skip_only_their_own_checks() {
+    let detector = ThreatDetector::new().unwrap();
+    let mut transpiler = transpiler(OutputScan::Block);
+    transpiler.configure_table(MappingTable {
+        mappings: [("∂", "open("), ("ƒ", "def")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        trusted: ["∂".to_string()].into_iter().collect(),
+        ..Default::default()
+    }).unwrap();
+
+    // The replacement and the output it produced are exempt, and noted.
+    assert_eq!(transpiler.transpile("ƒ f(): ∂'a')\n", &detector, false).unwrap(), "def f(): open('a')\n");
+    let exemptions = transpiler.take_exemptions();
+    assert_eq!(exemptions.iter().map(|e| e.stage).collect::<Vec<_>>(), [Stage::Replacement, Stage::Output]);
+
+    // Dangerous output from anywhere else is still blocked.
+    let source = "ƒ f(): ∂'a')\nƒ g(): open('b')\n";
+    let Err(Error::Security(violations)) = transpiler.transpile(source, &detector, false) else {
+        panic!("dangerous output allowed");
+    };
+    assert_eq!(violations.len(), 1);
+    assert_eq!((violations[0].stage, violations[0].line, violations[0].column), (Stage::Output, 2, 8));
+}
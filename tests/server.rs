@@ -0,0 +1,67 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::server::Server;
+use phirust_transpiler::{SymbolTranspiler, ThreatDetector};
+use serde_json::Value;
+
+fn serve(bypass: bool, requests: &[&str]) -> Vec<Value> {
+    let mut server = Server::new(SymbolTranspiler::new(), ThreatDetector::new().unwrap(), bypass);
+    let mut output = Vec::new();
+    server.run(requests.join("\n").as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+const BYPASSING: &str = r#"{"id":2,"method":"transpile","params":{"source":"x = 1","bypass":true}}"#;
+
+#[test]
+fn clients_cannot_bypass_unless_the_operator_did() {
+    let responses = serve(false, &[
+        r#"{"id":1,"method":"configure","params":{"symbols":{"ƒ":"def"},"bypass":true}}"#,
+        BYPASSING,
+        r#"{"id":3,"method":"transpile","params":{"source":"ƒ f(): pass","bypass":false}}"#,
+    ]);
+    assert_eq!(responses[0]["error"]["code"], "forbidden");
+    assert_eq!(responses[1]["error"]["code"], "forbidden");
+    // The refused configure request changed nothing.
+    assert_eq!(responses[2]["result"]["output"], "ƒ f(): pass");
+}
+
+#[test]
+fn operators_allow_bypassing() {
+    let responses = serve(true, &[
+        r#"{"id":1,"method":"configure","params":{"symbols":{"ƒ":"def"},"bypass":false}}"#,
+        BYPASSING,
+        r#"{"id":3,"method":"transpile","params":{"source":"ƒ f(): pass","bypass":"yes"}}"#,
+    ]);
+    assert!(responses[0]["result"].is_object());
+    assert_eq!(responses[1]["result"]["output"], "x = 1");
+    assert_eq!(responses[2]["error"]["code"], "invalid_params");
+}
+
+#[test]
+fn settings_alone_keep_the_mappings() {
+    let responses = serve(false, &[
+        r#"{"id":1,"method":"configure","params":{"dialect":"phicode"}}"#,
+        r#"{"id":2,"method":"configure","params":{"scan_output":"warn"}}"#,
+        r#"{"id":3,"method":"transpile","params":{"source":"ƒ f(): pass"}}"#,
+    ]);
+    assert!(responses[0]["result"]["symbols"].as_u64().unwrap() > 0);
+    assert_eq!(responses[1]["result"]["symbols"], responses[0]["result"]["symbols"]);
+    assert_eq!(responses[2]["result"]["output"], "def f(): pass");
+}
+
+#[test]
+fn refused_configures_change_nothing() {
+    let responses = serve(false, &[
+        r#"{"id":1,"method":"configure","params":{"symbols":{"ƒ":"def"}}}"#,
+        r#"{"id":2,"method":"check","params":{"code":"my_open(x)"}}"#,
+        r#"{"id":3,"method":"configure","params":{"detector":"ast","symbols":{"λ":"lambda"},"scan_output":"loud"}}"#,
+        r#"{"id":4,"method":"check","params":{"code":"my_open(x)"}}"#,
+        r#"{"id":5,"method":"transpile","params":{"source":"ƒ f(): λ"}}"#,
+    ]);
+    assert_eq!(responses[1]["result"]["dangerous"], true);
+    assert_eq!(responses[2]["error"]["code"], "invalid_params");
+    assert_eq!(responses[3]["result"]["dangerous"], true);
+    assert_eq!(responses[4]["result"]["output"], "def f(): λ");
+}
@@ -0,0 +1,91 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::policy::ThreatPolicy;
+use phirust_transpiler::threat_detector::DetectorBackend;
+use phirust_transpiler::{Error, OutputScan, SymbolTranspiler, ThreatDetector};
+use proptest::prelude::*;
+
+const MAPPINGS: &[(&str, &str)] = &[("ƒ", "def"), ("λ", "lambda"), ("⟲", "return"), ("∈", "in"), ("𝔼", "eval(")];
+
+// Statements whose lines must stay together: literals, brackets and
+// continuations spanning lines, and multi-byte characters everywhere.
+const FRAGMENTS: &[&str] = &[
+    "ƒ f(x):\n    ⟲ x\n",
+    "s = \"\"\"ƒ\n\nλ ∈\n\"\"\"\n",
+    "b = b'''\nƒ'''\n",
+    "t = f\"{λ} {{λ}}\"\n",
+    "xs = [\n    λ: 1,\n    2 ∈ s,\n]\n",
+    "y = 1 + \\\n    2 ∈ s\n",
+    "# λ ∈ ƒ\n",
+    "é = '∈'\n",
+    "\n",
+    "if x ∈ s:\n    pass\n",
+];
+
+fn transpiler() -> SymbolTranspiler {
+    let mut transpiler = SymbolTranspiler::new();
+    transpiler.configure(MAPPINGS.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()).unwrap();
+    transpiler
+}
+
+fn stream(transpiler: &mut SymbolTranspiler, source: &str, detector: &ThreatDetector, bypass: bool, chunk_size: usize) -> Result<String, Error> {
+    let mut output = Vec::new();
+    let read = transpiler.transpile_stream(source.as_bytes(), &mut output, detector, bypass, chunk_size)?;
+    assert_eq!(read, source.len());
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn pieces_never_cut_inside_a_statement() {
+    let detector = ThreatDetector::new().unwrap();
+    let source = FRAGMENTS.concat();
+    let whole = transpiler().transpile(&source, &detector, false).unwrap();
+    for chunk_size in 1..=16 {
+        assert_eq!(stream(&mut transpiler(), &source, &detector, false, chunk_size).unwrap(), whole, "chunk size {}", chunk_size);
+    }
+}
+
+#[test]
+fn findings_are_positioned_in_the_whole_input() {
+    let detector = ThreatDetector::new().unwrap();
+    let source = "x = [\n    1,\n]\ny = 𝔼'1')\n";
+    let Err(Error::Security(violations)) = stream(&mut transpiler(), source, &detector, false, 4) else {
+        panic!("dangerous replacement streamed");
+    };
+    assert_eq!(violations.len(), 1);
+    assert_eq!((violations[0].offset, violations[0].line, violations[0].column), (source.find('𝔼').unwrap(), 4, 5));
+}
+
+#[test]
+fn whole_input_checks_refuse_to_stream() {
+    let substring = ThreatDetector::new().unwrap();
+    let ast = ThreatDetector::with_backend(&ThreatPolicy::default(), DetectorBackend::Ast).unwrap();
+    let source = "e = 𝔼'1')\n";
+
+    for scan in [OutputScan::Warn, OutputScan::Block] {
+        let mut transpiler = transpiler();
+        transpiler.set_output_scan(scan);
+        let mut output = Vec::new();
+        assert!(matches!(transpiler.transpile_stream(source.as_bytes(), &mut output, &substring, false, 4), Err(Error::Unsupported(_))));
+        assert!(output.is_empty());
+    }
+    assert!(matches!(stream(&mut transpiler(), source, &ast, false, 4), Err(Error::Unsupported(_))));
+
+    let mut scanned = transpiler();
+    scanned.set_output_scan(OutputScan::Block);
+    assert_eq!(stream(&mut scanned, source, &ast, true, 4).unwrap(), "e = eval('1')\n");
+}
+
+proptest! {
+    #[test]
+    fn streaming_matches_whole_transpiles(
+        fragments in prop::collection::vec(prop::sample::select(FRAGMENTS), 0..12),
+        chunk_size in 1usize..16,
+    ) {
+        let detector = ThreatDetector::new().unwrap();
+        let source = fragments.concat();
+        let whole = transpiler().transpile(&source, &detector, false).unwrap();
+        prop_assert_eq!(stream(&mut transpiler(), &source, &detector, false, chunk_size).unwrap(), whole);
+    }
+}
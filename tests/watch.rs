@@ -0,0 +1,27 @@
+// Copyright 2025 Baleine Jay
+// Licensed under the Phicode Non-Commercial License (https://banes-lab.com/licensing)
+// Commercial use requires a paid license. See link for details.
+use phirust_transpiler::watch::{SourceWatcher, WatchBackend};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn polling_reports_changed_files() {
+    let dir = std::env::temp_dir().join(format!("phirust-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let interval = Duration::from_millis(20);
+    let watcher = SourceWatcher::new(&[dir.display().to_string()], interval, true).unwrap();
+    assert_eq!(watcher.backend(), WatchBackend::Polling(interval));
+
+    let (sender, changes) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(watcher.next_changes(Duration::from_millis(100)));
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    std::fs::write(dir.join("a.φ"), "ƒ f(): pass\n").unwrap();
+
+    let changed = changes.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+    assert!(changed.iter().any(|p| p.ends_with("a.φ")), "{:?}", changed);
+    std::fs::remove_dir_all(dir).unwrap();
+}